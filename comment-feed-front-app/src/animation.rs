@@ -0,0 +1,190 @@
+//! Fading and popping comments, worked out from how long they have been on screen.
+
+use comment_layout::{command::Size, Placement, Position};
+
+use crate::config::{Animation, Easing};
+
+/// How a comment is drawn at one point in its life.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Style {
+    /// Multiplies the comment's opacity.
+    pub alpha: f32,
+    /// Multiplies the comment's size, around its center.
+    pub scale: f32,
+}
+
+impl Style {
+    /// Top left corner of a comment of `size` at `position` once scaled.
+    pub fn origin(&self, position: (f32, f32), size: (f32, f32)) -> (f32, f32) {
+        let shrink = (1.0 - self.scale) / 2.0;
+        (position.0 + size.0 * shrink, position.1 + size.1 * shrink)
+    }
+}
+
+pub fn style<T>(animation: &Animation, placement: &Placement<T>, size: Size) -> Style {
+    let Placement {
+        elapsed, lifetime, ..
+    } = *placement;
+    let fade_out = match placement.position {
+        Position::Scroll => animation.fade_out,
+        Position::Top | Position::Bottom => animation.fixed_fade_out,
+    };
+
+    let appearing = progress(elapsed, animation.fade_in);
+    let disappearing = progress(lifetime - elapsed, fade_out);
+    let alpha = animation.easing.apply(appearing.min(disappearing));
+
+    let scale = if size == Size::Big {
+        let popped = animation
+            .easing
+            .apply(progress(elapsed, animation.pop_duration));
+        animation.pop_scale + (1.0 - animation.pop_scale) * popped
+    } else {
+        1.0
+    };
+
+    Style {
+        alpha: alpha.clamp(0.0, 1.0),
+        scale,
+    }
+}
+
+/// How far `elapsed` is into an effect lasting `duration`, from 0 to 1.
+fn progress(elapsed: f64, duration: f64) -> f32 {
+    if duration <= 0.0 {
+        1.0
+    } else {
+        (elapsed / duration).clamp(0.0, 1.0) as f32
+    }
+}
+
+impl Easing {
+    /// Maps linear progress from 0 to 1 onto the curve. Only `EaseOutBack` leaves that range.
+    pub fn apply(self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+            Easing::EaseOutBack => {
+                const OVERSHOOT: f32 = 1.70158;
+                let t = t - 1.0;
+                1.0 + (OVERSHOOT + 1.0) * t * t * t + OVERSHOOT * t * t
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASINGS: [Easing; 5] = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+        Easing::EaseOutBack,
+    ];
+
+    fn linear() -> Animation {
+        Animation {
+            easing: Easing::Linear,
+            ..Animation::default()
+        }
+    }
+
+    fn style_at(
+        animation: &Animation,
+        position: Position,
+        elapsed: f64,
+        lifetime: f64,
+        size: Size,
+    ) -> Style {
+        let placement = Placement {
+            item: &(),
+            x: 0.0,
+            y: 0.0,
+            position,
+            elapsed,
+            lifetime,
+        };
+        style(animation, &placement, size)
+    }
+
+    #[test]
+    fn easings_start_at_0_and_end_at_1() {
+        for easing in &EASINGS {
+            assert!(easing.apply(0.0).abs() < 1e-6, "{:?}", easing);
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-6, "{:?}", easing);
+        }
+        assert_eq!(Easing::Linear.apply(0.25), 0.25);
+        assert!(Easing::EaseOutBack.apply(0.8) > 1.0);
+    }
+
+    #[test]
+    fn zero_durations_are_done_at_once() {
+        assert_eq!(progress(0.0, 0.0), 1.0);
+        assert_eq!(progress(-1.0, 0.0), 1.0);
+        assert_eq!(progress(5.0, -1.0), 1.0);
+        assert_eq!(progress(0.1, 0.2), 0.5);
+        assert_eq!(progress(-0.1, 0.2), 0.0);
+        assert_eq!(progress(0.3, 0.2), 1.0);
+
+        let animation = Animation {
+            fade_in: 0.0,
+            fade_out: 0.0,
+            pop_duration: 0.0,
+            ..linear()
+        };
+        let start = style_at(&animation, Position::Scroll, 0.0, 10.0, Size::Big);
+        assert_eq!(
+            start,
+            Style {
+                alpha: 1.0,
+                scale: 1.0
+            }
+        );
+    }
+
+    #[test]
+    fn comments_fade_in_and_out() {
+        let animation = linear();
+        let alpha =
+            |elapsed| style_at(&animation, Position::Scroll, elapsed, 10.0, Size::Medium).alpha;
+        assert_eq!(alpha(0.0), 0.0);
+        assert_eq!(alpha(0.1), 0.5);
+        assert_eq!(alpha(5.0), 1.0);
+        assert!((alpha(9.9) - 0.5).abs() < 1e-5);
+        assert_eq!(alpha(10.0), 0.0);
+    }
+
+    #[test]
+    fn fixed_comments_fade_out_for_longer() {
+        let animation = linear();
+        let alpha = |position| style_at(&animation, position, 2.75, 3.0, Size::Medium).alpha;
+        assert_eq!(alpha(Position::Top), 0.5);
+        assert_eq!(alpha(Position::Bottom), 0.5);
+        assert_eq!(alpha(Position::Scroll), 1.0);
+    }
+
+    #[test]
+    fn big_comments_pop_from_pop_scale() {
+        let animation = linear();
+        let scale =
+            |elapsed, size| style_at(&animation, Position::Scroll, elapsed, 10.0, size).scale;
+        assert_eq!(scale(0.0, Size::Big), 1.5);
+        assert_eq!(scale(0.15, Size::Big), 1.25);
+        assert_eq!(scale(0.3, Size::Big), 1.0);
+        assert_eq!(scale(0.0, Size::Medium), 1.0);
+    }
+
+    #[test]
+    fn scaling_keeps_the_center() {
+        let style = Style {
+            alpha: 1.0,
+            scale: 2.0,
+        };
+        assert_eq!(style.origin((100.0, 50.0), (40.0, 20.0)), (80.0, 40.0));
+    }
+}
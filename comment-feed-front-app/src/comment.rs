@@ -0,0 +1,40 @@
+use comment_layout::Command;
+use serde_derive::Deserialize;
+
+/// A comment as received from the feed.
+pub struct Comment {
+    pub body: String,
+    pub command: Command,
+}
+
+/// Messages carrying commands are JSON objects, anything else is plain text.
+#[derive(Deserialize)]
+struct Payload {
+    body: String,
+    #[serde(default)]
+    command: String,
+}
+
+impl Comment {
+    pub fn parse(message: &str) -> Self {
+        match serde_json::from_str::<Payload>(message) {
+            Ok(payload) => Comment {
+                body: payload.body,
+                command: Command::parse(&payload.command),
+            },
+            Err(_) => Comment {
+                body: message.to_string(),
+                command: Command::default(),
+            },
+        }
+    }
+
+    /// Font size in pixels, given the size of `medium` comments.
+    pub fn scale(&self, font_size: f32) -> f32 {
+        font_size * self.command.size.scale()
+    }
+
+    pub fn color(&self) -> [f32; 4] {
+        self.command.color.to_rgba()
+    }
+}
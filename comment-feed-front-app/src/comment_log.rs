@@ -0,0 +1,109 @@
+//! Comments recorded as JSON lines, one [`Entry`] per line.
+
+use std::{
+    error::Error,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    time::Instant,
+};
+
+use chrono::{DateTime, Local};
+use log::info;
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Entry {
+    /// Seconds since the recording started.
+    pub elapsed: f64,
+    /// When the comment was received.
+    pub time: DateTime<Local>,
+    /// The message exactly as it came from the feed.
+    pub message: String,
+}
+
+/// Reads a whole log, sorted by `elapsed`. Blank lines are skipped.
+pub fn read(path: &Path) -> Result<Vec<Entry>, Box<dyn Error>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut entries = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(serde_json::from_str::<Entry>(&line)?);
+    }
+
+    entries.sort_by(|a, b| a.elapsed.partial_cmp(&b.elapsed).unwrap());
+    Ok(entries)
+}
+
+/// Appends received comments to a log file.
+pub struct Recorder {
+    writer: BufWriter<File>,
+    started: Instant,
+}
+
+impl Recorder {
+    /// Starts a new log in `dir`, named after the current time, so every session gets its own file.
+    /// Sessions started within the same second get a number after the time.
+    pub fn create(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let stem = format!("comments-{}", Local::now().format("%Y%m%d-%H%M%S"));
+
+        for number in 1.. {
+            let path = match number {
+                1 => dir.join(format!("{}.jsonl", stem)),
+                _ => dir.join(format!("{}-{}.jsonl", stem, number)),
+            };
+            let file = match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => file,
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(error) => return Err(error),
+            };
+            info!("recording comments to {:?}", path);
+
+            return Ok(Recorder {
+                writer: BufWriter::new(file),
+                started: Instant::now(),
+            });
+        }
+        unreachable!()
+    }
+
+    /// Writes one entry and flushes it, so the log survives the overlay being killed.
+    pub fn record(&mut self, message: &str) -> io::Result<()> {
+        let entry = Entry {
+            elapsed: self.started.elapsed().as_secs_f64(),
+            time: Local::now(),
+            message: message.to_string(),
+        };
+        serde_json::to_writer(&mut self.writer, &entry)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sessions_never_share_a_file() {
+        let dir = std::env::temp_dir().join(format!("comment-log-{}", std::process::id()));
+        let mut first = Recorder::create(&dir).unwrap();
+        first.record("first").unwrap();
+        let mut second = Recorder::create(&dir).unwrap();
+        second.record("second").unwrap();
+
+        let mut messages: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .flat_map(|file| read(&file.unwrap().path()).unwrap())
+            .map(|entry| entry.message)
+            .collect();
+        messages.sort();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(messages, ["first", "second"]);
+    }
+}
@@ -0,0 +1,298 @@
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use serde_derive::Deserialize;
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Fonts tried in order for every glyph; the first one that has the glyph wins.
+    pub fonts: Vec<FontSource>,
+    /// Font size of `medium` comments, in logical pixels or a percentage of the screen height.
+    pub font_size: Length,
+    pub effects: TextEffects,
+    pub animation: Animation,
+    /// Frame rate while comments are on screen. Nothing is drawn while the screen is empty.
+    pub fps: u32,
+    /// Directory to record received comments to, one file per session.
+    pub record_dir: Option<PathBuf>,
+    pub source: SourceConfig,
+    /// Monitor to cover, the primary one if unset.
+    pub monitor: Option<MonitorSelector>,
+    /// Part of the monitor comments are confined to.
+    pub region: Region,
+    /// Address of the control socket, like `127.0.0.1:7878`. There is none if unset.
+    pub control: Option<String>,
+    /// Secret control clients have to present. Only requests without an `Origin` are accepted
+    /// if unset.
+    pub control_token: Option<String>,
+    /// Images shown for `:name:` in comments, by name.
+    pub stamps: HashMap<String, PathBuf>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum MonitorSelector {
+    /// Position in the list of monitors logged at startup.
+    Index(usize),
+    /// Part of the monitor's name.
+    Name(String),
+}
+
+/// A rectangle relative to the monitor's top left corner.
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct Region {
+    pub x: Length,
+    pub y: Length,
+    pub width: Length,
+    pub height: Length,
+}
+
+/// Logical pixels as a number, or a percentage of the monitor size as a string like `"70%"`.
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(try_from = "LengthRepr")]
+pub enum Length {
+    Pixels(f32),
+    Fraction(f32),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LengthRepr {
+    Pixels(f32),
+    Percent(String),
+}
+
+/// A rectangle in framebuffer pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Area {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SourceConfig {
+    #[serde(rename = "websocket")]
+    WebSocket {
+        url: String,
+        #[serde(default = "default_channel")]
+        channel: String,
+    },
+    /// One message per line.
+    Stdin,
+    /// One message per line from every client.
+    UnixSocket { path: PathBuf },
+    /// Lines appended to a file.
+    TailFile { path: PathBuf },
+    /// A recorded comment log.
+    Replay {
+        path: PathBuf,
+        #[serde(default = "default_speed")]
+        speed: f64,
+        /// Seconds into the log to start from.
+        #[serde(default)]
+        seek: f64,
+    },
+}
+
+fn default_channel() -> String {
+    "test".into()
+}
+
+fn default_speed() -> f64 {
+    1.0
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FontSource {
+    /// DejaVu Sans embedded in the binary.
+    Bundled,
+    /// A font file on disk.
+    File(PathBuf),
+    /// A family name resolved through fontconfig.
+    System(String),
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct TextEffects {
+    pub outline: Option<Outline>,
+    pub shadow: Option<Shadow>,
+}
+
+/// Stroke drawn around every glyph.
+#[derive(Deserialize, Clone, Copy)]
+pub struct Outline {
+    /// In pixels.
+    pub width: f32,
+    pub color: [f32; 4],
+}
+
+/// Copy of the text drawn behind it.
+#[derive(Deserialize, Clone, Copy)]
+pub struct Shadow {
+    /// In pixels, positive values go right and down.
+    pub offset: (f32, f32),
+    pub color: [f32; 4],
+}
+
+/// How comments appear and disappear. Durations are in seconds, 0 turns an effect off.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct Animation {
+    pub fade_in: f64,
+    /// Fade at the end of scrolling comments.
+    pub fade_out: f64,
+    /// Fade at the end of top and bottom comments.
+    pub fixed_fade_out: f64,
+    /// Big comments start this many times their size and shrink back.
+    pub pop_scale: f32,
+    pub pop_duration: f64,
+    pub easing: Easing,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    /// Overshoots a little before settling.
+    EaseOutBack,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Animation {
+            fade_in: 0.2,
+            fade_out: 0.2,
+            fixed_fade_out: 0.5,
+            pop_scale: 1.5,
+            pop_duration: 0.3,
+            easing: Easing::EaseOut,
+        }
+    }
+}
+
+impl Default for TextEffects {
+    fn default() -> Self {
+        TextEffects {
+            outline: Some(Outline {
+                width: 2.0,
+                color: [0.0, 0.0, 0.0, 1.0],
+            }),
+            shadow: None,
+        }
+    }
+}
+
+impl TryFrom<LengthRepr> for Length {
+    type Error = String;
+
+    fn try_from(repr: LengthRepr) -> Result<Self, Self::Error> {
+        match repr {
+            LengthRepr::Pixels(pixels) => Ok(Length::Pixels(pixels)),
+            LengthRepr::Percent(percent) => percent
+                .strip_suffix('%')
+                .and_then(|percent| percent.trim().parse::<f32>().ok())
+                .map(|percent| Length::Fraction(percent / 100.0))
+                .ok_or_else(|| format!("expected a number or a percentage, got {:?}", percent)),
+        }
+    }
+}
+
+impl Length {
+    /// In physical pixels, given the physical size a percentage is of.
+    pub fn resolve(self, total: f32, scale_factor: f32) -> f32 {
+        match self {
+            Length::Pixels(pixels) => pixels * scale_factor,
+            Length::Fraction(fraction) => total * fraction,
+        }
+    }
+}
+
+impl Default for Region {
+    fn default() -> Self {
+        Region {
+            x: Length::Fraction(0.0),
+            y: Length::Fraction(0.0),
+            width: Length::Fraction(1.0),
+            height: Length::Fraction(1.0),
+        }
+    }
+}
+
+impl Region {
+    /// The region on a screen of `size` pixels, clipped to the screen.
+    pub fn resolve(&self, size: (f32, f32), scale_factor: f32) -> Area {
+        let x = self.x.resolve(size.0, scale_factor).max(0.0).min(size.0);
+        let y = self.y.resolve(size.1, scale_factor).max(0.0).min(size.1);
+        Area {
+            x,
+            y,
+            width: self
+                .width
+                .resolve(size.0, scale_factor)
+                .max(0.0)
+                .min(size.0 - x),
+            height: self
+                .height
+                .resolve(size.1, scale_factor)
+                .max(0.0)
+                .min(size.1 - y),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            fonts: vec![
+                FontSource::Bundled,
+                FontSource::System("Noto Sans CJK JP".into()),
+                FontSource::System("Noto Emoji".into()),
+            ],
+            font_size: Length::Pixels(comment_layout::FONT_SIZE),
+            effects: TextEffects::default(),
+            animation: Animation::default(),
+            fps: 60,
+            record_dir: None,
+            source: SourceConfig::WebSocket {
+                url: "wss://7ht6ij8i09.execute-api.ap-northeast-1.amazonaws.com/production".into(),
+                channel: default_channel(),
+            },
+            monitor: None,
+            region: Region::default(),
+            control: None,
+            control_token: None,
+            stamps: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let reader = BufReader::new(File::open(path)?);
+        let config: Config = serde_json::from_reader(reader)?;
+        if config.fps == 0 {
+            return Err("fps must be at least 1".into());
+        }
+        if let SourceConfig::Replay { speed, .. } = config.source {
+            if !(speed.is_finite() && speed > 0.0) {
+                return Err("replay speed must be above 0".into());
+            }
+        }
+        Ok(config)
+    }
+}
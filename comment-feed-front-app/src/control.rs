@@ -0,0 +1,384 @@
+//! Commands that change a running overlay, from its keyboard shortcuts or the control socket.
+//!
+//! The socket takes one command per line and answers `ok` or `error: ...` to each of them. Plain
+//! HTTP POST requests work too, with the command's words as the path, so
+//! `curl -X POST localhost:7878/opacity/0.5` does the same as sending `opacity 0.5`.
+
+use std::{
+    error::Error,
+    fmt,
+    io::{BufRead, BufReader, Write},
+    net::{IpAddr, TcpListener, TcpStream},
+    thread,
+};
+
+use glium::glutin::event_loop::EventLoopProxy;
+use log::{info, warn};
+
+use crate::UserEvent;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Control {
+    Pause,
+    Resume,
+    TogglePause,
+    /// Removes every comment on screen.
+    Clear,
+    Hide,
+    Show,
+    ToggleVisibility,
+    /// Sets the opacity of all comments, from 0 to 1.
+    Opacity(f32),
+    /// Changes the opacity by this much.
+    OpacityBy(f32),
+    /// Moves a replay by this many seconds.
+    Seek(f64),
+    /// Moves the websocket feed to another channel.
+    Channel(String),
+}
+
+impl Control {
+    /// Parses commands like `pause`, `opacity 0.5`, `opacity -0.1` or `channel test`.
+    pub fn parse(command: &str) -> Result<Self, String> {
+        let mut words = command.split_whitespace();
+        let name = words.next().ok_or("empty command")?;
+        let argument = words.next();
+        let number = |argument: Option<&str>| {
+            argument
+                .and_then(|argument| argument.parse::<f64>().ok())
+                .filter(|number| number.is_finite())
+                .ok_or(format!("{} needs a number", name))
+        };
+
+        let control = match name {
+            "pause" => Control::Pause,
+            "resume" => Control::Resume,
+            "toggle-pause" => Control::TogglePause,
+            "clear" => Control::Clear,
+            "hide" => Control::Hide,
+            "show" => Control::Show,
+            "toggle-visibility" => Control::ToggleVisibility,
+            // a sign makes it relative
+            "opacity" if argument.is_some_and(|argument| argument.starts_with(&['+', '-'][..])) => {
+                Control::OpacityBy(number(argument)? as f32)
+            }
+            "opacity" => Control::Opacity(number(argument)? as f32),
+            "seek" => Control::Seek(number(argument)?),
+            "channel" => Control::Channel(argument.ok_or("channel needs a name")?.to_string()),
+            _ => return Err(format!("unknown command {:?}", name)),
+        };
+
+        // the word after a command without arguments is extra too
+        let extra = match control {
+            Control::Opacity(_)
+            | Control::OpacityBy(_)
+            | Control::Seek(_)
+            | Control::Channel(_) => words.next(),
+            _ => argument,
+        };
+        match extra {
+            Some(extra) => Err(format!("unexpected {:?}", extra)),
+            None => Ok(control),
+        }
+    }
+}
+
+impl fmt::Display for Control {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Control::Pause => write!(f, "pause"),
+            Control::Resume => write!(f, "resume"),
+            Control::TogglePause => write!(f, "toggle-pause"),
+            Control::Clear => write!(f, "clear"),
+            Control::Hide => write!(f, "hide"),
+            Control::Show => write!(f, "show"),
+            Control::ToggleVisibility => write!(f, "toggle-visibility"),
+            Control::Opacity(opacity) => write!(f, "opacity {}", opacity),
+            Control::OpacityBy(change) => write!(f, "opacity {:+}", change),
+            Control::Seek(seconds) => write!(f, "seek {}", seconds),
+            Control::Channel(channel) => write!(f, "channel {}", channel),
+        }
+    }
+}
+
+/// Accepts control connections on `address` on a thread of its own. With a `token`, clients have
+/// to present it before anything else.
+pub fn listen(
+    address: &str,
+    token: Option<String>,
+    proxy: EventLoopProxy<UserEvent>,
+) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind(address)?;
+    info!("control socket listening on {}", listener.local_addr()?);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let proxy = proxy.clone();
+            let token = token.clone();
+            match stream {
+                Ok(stream) => {
+                    thread::spawn(move || {
+                        if let Err(error) = serve(stream, token.as_deref(), &proxy) {
+                            warn!("control client failed: {}", error);
+                        }
+                    });
+                }
+                Err(error) => warn!("failed to accept control client: {}", error),
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn serve(
+    stream: TcpStream,
+    token: Option<&str>,
+    proxy: &EventLoopProxy<UserEvent>,
+) -> Result<(), Box<dyn Error>> {
+    let mut writer = stream.try_clone()?;
+    let mut lines = BufReader::new(stream).lines();
+    let mut authorized = token.is_none();
+
+    while let Some(line) = lines.next() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some((method, path)) = http_request(&line) {
+            // the request has to be read before answering
+            let mut headers = Vec::new();
+            for header in &mut lines {
+                let header = header?;
+                if header.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+                }
+            }
+            let header = |name: &str| {
+                headers
+                    .iter()
+                    .find(|(header, _)| header == name)
+                    .map(|(_, value)| value.as_str())
+            };
+
+            let (status, body) = match check_http(method, header, token) {
+                Err(status) => (status, format!("error: {}\n", status)),
+                Ok(()) => match send(&percent_decode(path).replace('/', " "), proxy) {
+                    Ok(()) => ("200 OK", "ok\n".to_string()),
+                    Err(error) => ("400 Bad Request", format!("error: {}\n", error)),
+                },
+            };
+            write!(
+                writer,
+                "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )?;
+            return Ok(());
+        }
+
+        if !authorized {
+            // the first line has to be `token <secret>`
+            if line.strip_prefix("token ").map(str::trim) == token {
+                authorized = true;
+                writeln!(writer, "ok")?;
+                continue;
+            }
+            writeln!(writer, "error: expected the control token")?;
+            return Ok(());
+        }
+
+        match send(&line, proxy) {
+            Ok(()) => writeln!(writer, "ok")?,
+            Err(error) => writeln!(writer, "error: {}", error)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Keeps web pages from controlling the overlay. They can't set `Authorization` without a CORS
+/// preflight this socket never answers, always send `Origin` with a POST, and only reach it under
+/// another `Host` by rebinding a domain name to it.
+fn check_http<'a>(
+    method: &str,
+    header: impl Fn(&str) -> Option<&'a str>,
+    token: Option<&str>,
+) -> Result<(), &'static str> {
+    if method != "POST" {
+        return Err("405 Method Not Allowed");
+    }
+    if !header("host").is_some_and(is_local_host) {
+        return Err("403 Forbidden");
+    }
+    match token {
+        Some(token) => {
+            if header("authorization").and_then(|value| value.strip_prefix("Bearer "))
+                != Some(token)
+            {
+                return Err("401 Unauthorized");
+            }
+        }
+        None => {
+            if header("origin").is_some() {
+                return Err("403 Forbidden");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `localhost` or an IP address, with or without a port.
+fn is_local_host(host: &str) -> bool {
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if !name.ends_with(':') && port.parse::<u16>().is_ok() => name,
+        _ => host,
+    };
+    let name = name.trim_start_matches('[').trim_end_matches(']');
+    name.eq_ignore_ascii_case("localhost") || name.parse::<IpAddr>().is_ok()
+}
+
+fn send(command: &str, proxy: &EventLoopProxy<UserEvent>) -> Result<(), String> {
+    let control = Control::parse(command)?;
+    info!("control: {}", control);
+    proxy
+        .send_event(UserEvent::Control(control))
+        .map_err(|_| "overlay closed".to_string())
+}
+
+/// The method and path of an HTTP request line like `POST /pause HTTP/1.1`, without the path's
+/// leading slash.
+fn http_request(line: &str) -> Option<(&str, &str)> {
+    let mut parts = line.split_whitespace();
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(path), Some(version))
+            if path.starts_with('/') && version.starts_with("HTTP/") =>
+        {
+            Some((method, path.trim_start_matches('/')))
+        }
+        _ => None,
+    }
+}
+
+/// Decodes `%XX` escapes, so `/channel/%E6%97%A5` names the channel `日`.
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers<'a>(headers: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<&'a str> {
+        move |name| {
+            headers
+                .iter()
+                .find(|(header, _)| *header == name)
+                .map(|(_, value)| *value)
+        }
+    }
+
+    #[test]
+    fn commands_parse() {
+        assert_eq!(Control::parse(" pause "), Ok(Control::Pause));
+        assert_eq!(Control::parse("opacity 0.5"), Ok(Control::Opacity(0.5)));
+        assert_eq!(Control::parse("opacity +0.1"), Ok(Control::OpacityBy(0.1)));
+        assert_eq!(Control::parse("opacity -0.1"), Ok(Control::OpacityBy(-0.1)));
+        assert_eq!(Control::parse("seek -10"), Ok(Control::Seek(-10.0)));
+        assert_eq!(
+            Control::parse("channel 日本語"),
+            Ok(Control::Channel("日本語".to_string()))
+        );
+    }
+
+    #[test]
+    fn bad_commands_are_rejected() {
+        for command in &[
+            "",
+            "jump",
+            "opacity",
+            "opacity half",
+            "opacity nan",
+            "opacity +inf",
+            "seek NaN",
+            "channel",
+            "pause now",
+            "opacity 0.5 0.6",
+            "channel a b",
+        ] {
+            assert!(Control::parse(command).is_err(), "{:?} parsed", command);
+        }
+    }
+
+    #[test]
+    fn commands_format_back_to_themselves() {
+        for command in &[
+            "toggle-visibility",
+            "opacity 0.5",
+            "opacity -0.1",
+            "seek 10",
+        ] {
+            assert_eq!(Control::parse(command).unwrap().to_string(), *command);
+        }
+    }
+
+    #[test]
+    fn only_local_posts_without_origin_pass() {
+        let local = [("host", "127.0.0.1:7878")];
+        assert_eq!(check_http("POST", headers(&local), None), Ok(()));
+        assert!(check_http("GET", headers(&local), None).is_err());
+        assert!(check_http("POST", headers(&[]), None).is_err());
+        assert!(check_http("POST", headers(&[("host", "evil.example:7878")]), None).is_err());
+        let from_page = [
+            ("host", "localhost:7878"),
+            ("origin", "https://evil.example"),
+        ];
+        assert!(check_http("POST", headers(&from_page), None).is_err());
+    }
+
+    #[test]
+    fn a_token_is_required_once_set() {
+        let without = [("host", "[::1]:7878")];
+        assert!(check_http("POST", headers(&without), Some("secret")).is_err());
+        let with = [
+            ("host", "localhost"),
+            ("origin", "https://dock.example"),
+            ("authorization", "Bearer secret"),
+        ];
+        assert_eq!(check_http("POST", headers(&with), Some("secret")), Ok(()));
+    }
+
+    #[test]
+    fn paths_are_percent_decoded() {
+        assert_eq!(
+            percent_decode("channel/%E6%97%A5%E6%9C%AC%E8%AA%9E"),
+            "channel/日本語"
+        );
+        assert_eq!(percent_decode("opacity/50%"), "opacity/50%");
+        assert_eq!(percent_decode("%zz%4"), "%zz%4");
+    }
+}
@@ -0,0 +1,307 @@
+//! Converts a comment log into subtitles, so the comment track can be muxed into or burned onto
+//! a recording.
+
+use std::{error::Error, fmt::Write, str::FromStr};
+
+use comment_layout::{command::Color, Layout, Position};
+use glium_glyph::glyph_brush::GlyphCalculatorBuilder;
+
+use crate::{comment::Comment, comment_log::Entry, config::Config, font::FontSet, text};
+
+pub enum Format {
+    /// Advanced SubStation Alpha, with the same motion and lanes as the overlay.
+    Ass,
+    /// SubRip, text and timing only.
+    Srt,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "ass" => Ok(Format::Ass),
+            "srt" => Ok(Format::Srt),
+            _ => Err(format!("unknown format {:?}, expected ass or srt", format)),
+        }
+    }
+}
+
+/// A comment laid out the way the overlay would have shown it.
+struct Event {
+    comment: Comment,
+    start: f64,
+    end: f64,
+    x: f32,
+    y: f32,
+    width: f32,
+}
+
+pub fn export(
+    config: &Config,
+    entries: &[Entry],
+    format: Format,
+    size: (u32, u32),
+    font_name: &str,
+) -> Result<String, Box<dyn Error>> {
+    let font_set = FontSet::load(&config.fonts);
+    let calculator = GlyphCalculatorBuilder::using_fonts(font_set.fonts().to_vec()).build();
+    let mut glyphs = calculator.cache_scope();
+    let bounds = (size.0 as f32, size.1 as f32);
+    let metrics = text::Metrics::new(config, bounds.1, 1.0);
+    let mut layout = Layout::<()>::new(text::layout_config(&font_set, bounds, &metrics));
+
+    let mut events = Vec::new();
+    for entry in entries {
+        let comment = Comment::parse(&entry.message);
+        let (width, height) = text::measure(
+            &mut glyphs,
+            &font_set,
+            &comment.body,
+            comment.scale(metrics.font_size),
+            bounds,
+        );
+
+        layout.expire(entry.elapsed);
+        layout.place((), comment.command.position, width, height, entry.elapsed);
+        let placement = layout.positions(entry.elapsed).last().unwrap();
+
+        events.push(Event {
+            start: entry.elapsed,
+            end: entry.elapsed + placement.lifetime,
+            x: placement.x,
+            y: placement.y,
+            width,
+            comment,
+        });
+    }
+
+    Ok(match format {
+        Format::Ass => ass(config, &metrics, &events, size, font_name),
+        Format::Srt => srt(&events),
+    })
+}
+
+fn ass(
+    config: &Config,
+    metrics: &text::Metrics,
+    events: &[Event],
+    size: (u32, u32),
+    font_name: &str,
+) -> String {
+    let mut out = String::new();
+    let outline = config.effects.outline;
+    let shadow = config.effects.shadow;
+
+    writeln!(out, "[Script Info]").unwrap();
+    writeln!(out, "ScriptType: v4.00+").unwrap();
+    writeln!(out, "PlayResX: {}", size.0).unwrap();
+    writeln!(out, "PlayResY: {}", size.1).unwrap();
+    writeln!(out, "WrapStyle: 2").unwrap();
+    writeln!(out, "ScaledBorderAndShadow: yes").unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "[V4+ Styles]").unwrap();
+    writeln!(out, "Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding").unwrap();
+    writeln!(
+        out,
+        "Style: Default,{},{},{},{},{},{},0,0,0,0,100,100,0,0,1,{},0,7,0,0,0,1",
+        font_name,
+        metrics.font_size,
+        ass_color(Color::default().to_rgba()),
+        ass_color(Color::default().to_rgba()),
+        ass_color(outline.map_or([0.0; 4], |outline| outline.color)),
+        ass_color(shadow.map_or([0.0; 4], |shadow| shadow.color)),
+        outline.map_or(0.0, |outline| outline.width),
+    )
+    .unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "[Events]").unwrap();
+    writeln!(
+        out,
+        "Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text"
+    )
+    .unwrap();
+
+    for event in events {
+        let mut overrides = match event.comment.command.position {
+            Position::Scroll => format!(
+                "\\move({},{:.0},{:.0},{:.0})",
+                size.0, event.y, -event.width, event.y
+            ),
+            Position::Top | Position::Bottom => format!("\\pos({:.0},{:.0})", event.x, event.y),
+        };
+        let fade_out = match event.comment.command.position {
+            Position::Scroll => config.animation.fade_out,
+            Position::Top | Position::Bottom => config.animation.fixed_fade_out,
+        };
+        if config.animation.fade_in > 0.0 || fade_out > 0.0 {
+            write!(
+                overrides,
+                "\\fad({:.0},{:.0})",
+                config.animation.fade_in * 1000.0,
+                fade_out * 1000.0
+            )
+            .unwrap();
+        }
+        if let Some(shadow) = shadow {
+            write!(
+                overrides,
+                "\\xshad{}\\yshad{}",
+                shadow.offset.0, shadow.offset.1
+            )
+            .unwrap();
+        }
+        if event.comment.command.color != Color::default() {
+            write!(overrides, "\\c{}", ass_rgb(event.comment.command.color)).unwrap();
+        }
+        let font_size = event.comment.scale(metrics.font_size);
+        if font_size != metrics.font_size {
+            write!(overrides, "\\fs{}", font_size).unwrap();
+        }
+
+        writeln!(
+            out,
+            "Dialogue: 0,{},{},Default,,0,0,0,,{{{}}}{}",
+            ass_time(event.start),
+            ass_time(event.end),
+            overrides,
+            ass_escape(&event.comment.body),
+        )
+        .unwrap();
+    }
+
+    out
+}
+
+fn srt(events: &[Event]) -> String {
+    let mut out = String::new();
+
+    for (index, event) in events.iter().enumerate() {
+        writeln!(out, "{}", index + 1).unwrap();
+        writeln!(out, "{} --> {}", srt_time(event.start), srt_time(event.end)).unwrap();
+
+        // most players understand this ASS tag, subtitles are at the bottom anyway otherwise
+        if event.comment.command.position == Position::Top {
+            write!(out, "{{\\an8}}").unwrap();
+        }
+        let color = event.comment.command.color;
+        let body = srt_escape(&event.comment.body);
+        if color == Color::default() {
+            writeln!(out, "{}", body).unwrap();
+        } else {
+            writeln!(
+                out,
+                "<font color=\"#{:02x}{:02x}{:02x}\">{}</font>",
+                color.0, color.1, color.2, body
+            )
+            .unwrap();
+        }
+        writeln!(out).unwrap();
+    }
+
+    out
+}
+
+/// `&HAABBGGRR`, where alpha 0 is opaque.
+fn ass_color([r, g, b, a]: [f32; 4]) -> String {
+    let byte = |channel: f32| (channel.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!(
+        "&H{:02X}{:02X}{:02X}{:02X}",
+        255 - byte(a),
+        byte(b),
+        byte(g),
+        byte(r)
+    )
+}
+
+/// `&HBBGGRR&`, as used by override tags.
+fn ass_rgb(Color(r, g, b): Color) -> String {
+    format!("&H{:02X}{:02X}{:02X}&", b, g, r)
+}
+
+/// `h:mm:ss.cc`
+fn ass_time(seconds: f64) -> String {
+    let centiseconds = (seconds * 100.0).round() as u64;
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        centiseconds / 360_000,
+        centiseconds / 6_000 % 60,
+        centiseconds / 100 % 60,
+        centiseconds % 100
+    )
+}
+
+/// `hh:mm:ss,mmm`
+fn srt_time(seconds: f64) -> String {
+    let milliseconds = (seconds * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        milliseconds / 3_600_000,
+        milliseconds / 60_000 % 60,
+        milliseconds / 1000 % 60,
+        milliseconds % 1000
+    )
+}
+
+/// Keeps comment text from being read as override blocks or line breaks.
+/// A word joiner after each backslash stops sequences like `\n` from being interpreted.
+fn ass_escape(body: &str) -> String {
+    body.replace('\\', "\\\u{2060}")
+        .replace('{', "\\{")
+        .replace('}', "\\}")
+        .replace('\n', "\\N")
+}
+
+/// Keeps comment text from being read as tags.
+fn srt_escape(body: &str) -> String {
+    body.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ass_times_round_to_centiseconds() {
+        assert_eq!(ass_time(0.0), "0:00:00.00");
+        assert_eq!(ass_time(1.004), "0:00:01.00");
+        assert_eq!(ass_time(1.006), "0:00:01.01");
+        // halves round up, 0.125 is exact in binary
+        assert_eq!(ass_time(0.125), "0:00:00.13");
+        assert_eq!(ass_time(59.999), "0:01:00.00");
+        assert_eq!(ass_time(3723.45), "1:02:03.45");
+    }
+
+    #[test]
+    fn srt_times_round_to_milliseconds() {
+        assert_eq!(srt_time(0.0), "00:00:00,000");
+        assert_eq!(srt_time(0.0004), "00:00:00,000");
+        assert_eq!(srt_time(0.0625), "00:00:00,063");
+        assert_eq!(srt_time(59.9996), "00:01:00,000");
+        assert_eq!(srt_time(3723.456), "01:02:03,456");
+    }
+
+    #[test]
+    fn ass_text_cannot_open_override_blocks() {
+        assert_eq!(ass_escape("{\\b1}bold"), "\\{\\\u{2060}b1\\}bold");
+        assert_eq!(ass_escape("a\\nb"), "a\\\u{2060}nb");
+        assert_eq!(ass_escape("two\nlines"), "two\\Nlines");
+    }
+
+    #[test]
+    fn srt_text_cannot_open_tags() {
+        assert_eq!(srt_escape("<b>&</b>"), "&lt;b&gt;&amp;&lt;/b&gt;");
+    }
+
+    #[test]
+    fn ass_colors_are_alpha_blue_green_red() {
+        assert_eq!(ass_color([1.0, 0.5, 0.0, 1.0]), "&H000080FF");
+        assert_eq!(ass_color([0.0, 0.0, 0.0, 0.0]), "&HFF000000");
+        assert_eq!(ass_color([2.0, -1.0, 0.0, 1.0]), "&H000000FF");
+        assert_eq!(ass_rgb(Color(0x12, 0x34, 0x56)), "&H563412&");
+    }
+}
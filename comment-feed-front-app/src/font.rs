@@ -0,0 +1,169 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use glium_glyph::glyph_brush::FontId;
+use log::{info, warn};
+use rusttype::{Font, FontCollection, GlyphId, Scale};
+
+use crate::config::FontSource;
+
+const DEJAVU: &[u8] = include_bytes!("../resource/fonts/DejaVuSans-2.37.ttf");
+
+/// Fonts in fallback order. `FontId(n)` refers to `fonts()[n]`.
+pub struct FontSet {
+    fonts: Vec<Font<'static>>,
+}
+
+impl FontSet {
+    /// Loads every source that can be loaded, skipping the rest with a warning.
+    /// The bundled font is appended when nothing else could be loaded.
+    pub fn load(sources: &[FontSource]) -> Self {
+        let mut fonts = Vec::new();
+        // `None` stands for the bundled font.
+        let mut loaded = Vec::<Option<(PathBuf, usize)>>::new();
+
+        for source in sources {
+            let file = match source {
+                FontSource::Bundled => None,
+                FontSource::File(path) => Some((path.clone(), 0)),
+                FontSource::System(family) => match find_system_font(family) {
+                    Some(file) => {
+                        info!("font {:?} resolved to {:?}", family, file.0);
+                        Some(file)
+                    }
+                    None => {
+                        warn!("font {:?} not found on this system", family);
+                        continue;
+                    }
+                },
+            };
+
+            // fontconfig falls back to some default font for unknown families,
+            // which is likely to be in the list already.
+            if loaded.contains(&file) {
+                continue;
+            }
+
+            let font = match &file {
+                None => Font::from_bytes(DEJAVU).ok(),
+                Some((path, index)) => load_file(path, *index),
+            };
+            loaded.push(file);
+
+            match font {
+                Some(font) => fonts.push(font),
+                None => warn!("failed to load font {:?}", source),
+            }
+        }
+
+        if fonts.is_empty() {
+            fonts.push(Font::from_bytes(DEJAVU).unwrap());
+        }
+
+        FontSet { fonts }
+    }
+
+    pub fn fonts(&self) -> &[Font<'static>] {
+        &self.fonts
+    }
+
+    /// Splits `text` into runs that can each be drawn with a single font.
+    ///
+    /// Each character goes to the first font that has a glyph for it. Whitespace stays in the
+    /// current run so that spaces don't break runs apart. Characters no font knows fall back
+    /// to the first font.
+    pub fn runs<'a>(&self, text: &'a str) -> Vec<(&'a str, FontId)> {
+        let mut runs = Vec::new();
+        let mut run_start = 0;
+        let mut run_font = None;
+
+        for (index, c) in text.char_indices() {
+            let font = match run_font {
+                Some(current) if c.is_whitespace() || self.has_glyph(current, c) => current,
+                _ => self.font_for(c),
+            };
+
+            match run_font {
+                Some(current) if current != font => {
+                    runs.push((&text[run_start..index], FontId(current)));
+                    run_start = index;
+                }
+                _ => (),
+            }
+            run_font = Some(font);
+        }
+
+        if let Some(current) = run_font {
+            runs.push((&text[run_start..], FontId(current)));
+        }
+
+        runs
+    }
+
+    /// How far the pen moves drawing `text` at `scale`, trailing whitespace included.
+    pub fn advance(&self, text: &str, scale: f32) -> f32 {
+        let scale = Scale::uniform(scale);
+        self.runs(text)
+            .into_iter()
+            .map(|(run, FontId(index))| {
+                let font = &self.fonts[index];
+                let mut previous = None;
+                run.chars()
+                    .map(|c| {
+                        let glyph = font.glyph(c).scaled(scale);
+                        let kerning = previous.map_or(0.0, |previous| {
+                            font.pair_kerning(scale, previous, glyph.id())
+                        });
+                        previous = Some(glyph.id());
+                        kerning + glyph.h_metrics().advance_width
+                    })
+                    .sum::<f32>()
+            })
+            .sum()
+    }
+
+    fn font_for(&self, c: char) -> usize {
+        (0..self.fonts.len())
+            .find(|&index| self.has_glyph(index, c))
+            .unwrap_or(0)
+    }
+
+    fn has_glyph(&self, index: usize, c: char) -> bool {
+        self.fonts[index].glyph(c).id() != GlyphId(0)
+    }
+}
+
+fn load_file(path: &Path, index: usize) -> Option<Font<'static>> {
+    let data = fs::read(path)
+        .map_err(|error| warn!("failed to read {:?}: {}", path, error))
+        .ok()?;
+
+    FontCollection::from_bytes(data)
+        .and_then(|collection| collection.font_at(index))
+        .map_err(|error| warn!("failed to parse {:?}: {}", path, error))
+        .ok()
+}
+
+/// Asks fontconfig for the file (and face index within it) that best matches `family`.
+fn find_system_font(family: &str) -> Option<(PathBuf, usize)> {
+    let output = Command::new("fc-match")
+        .arg("--format=%{index}\t%{file}")
+        .arg(family)
+        .output()
+        .map_err(|error| warn!("failed to run fc-match: {}", error))
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let output = String::from_utf8(output.stdout).ok()?;
+    let mut fields = output.splitn(2, '\t');
+    let index = fields.next()?.parse().ok()?;
+    let file = fields.next().filter(|file| !file.is_empty())?;
+
+    Some((PathBuf::from(file), index))
+}
@@ -0,0 +1,226 @@
+//! Renders a comment log into transparent PNG frames on the CPU, so that clips can be made and
+//! the overlay can be checked without a display or a GPU.
+
+use std::{collections::HashMap, error::Error, fs, path::Path};
+
+use comment_layout::Layout;
+use glium_glyph::glyph_brush::GlyphCalculatorBuilder;
+use image::{imageops, RgbaImage};
+use log::info;
+
+use crate::{
+    animation,
+    comment::Comment,
+    comment_log::Entry,
+    config::Config,
+    font::FontSet,
+    inline::{Content, Line, Picture, Pictures},
+    text,
+};
+
+/// Writes `frame-000000.png`, `frame-000001.png`, ... into `out` until the last comment has left
+/// the screen.
+pub fn render(
+    config: &Config,
+    entries: &[Entry],
+    out: &Path,
+    fps: u32,
+    size: (u32, u32),
+) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(out)?;
+
+    let font_set = FontSet::load(&config.fonts);
+    let calculator = GlyphCalculatorBuilder::using_fonts(font_set.fonts().to_vec()).build();
+    let bounds = (size.0 as f32, size.1 as f32);
+    // frames have no scale factor, logical pixels are pixels
+    let metrics = text::Metrics::new(config, bounds.1, 1.0);
+    let mut layout =
+        Layout::<(Comment, Line)>::new(text::layout_config(&font_set, bounds, &metrics));
+    let mut pictures = Pictures::new(config.stamps.clone());
+    // resized once per picture and size rather than every frame
+    let mut scaled = HashMap::<(Picture, u32, u32), RgbaImage>::new();
+    let mut pending = entries.iter().peekable();
+
+    for frame in 0.. {
+        let time = f64::from(frame) / f64::from(fps);
+        let mut glyphs = calculator.cache_scope();
+
+        while let Some(entry) = pending.peek() {
+            if entry.elapsed > time {
+                break;
+            }
+            let comment = Comment::parse(&entry.message);
+            let position = comment.command.position;
+            let line = Line::layout(
+                &mut glyphs,
+                &font_set,
+                &mut pictures,
+                &comment,
+                &metrics,
+                &config.effects,
+                bounds,
+            );
+            let (width, height) = (line.width, line.height);
+            layout.place((comment, line), position, width, height, entry.elapsed);
+            pending.next();
+        }
+
+        layout.expire(time);
+        if layout.is_empty() && pending.peek().is_none() {
+            info!("rendered {} frames", frame);
+            break;
+        }
+
+        let mut canvas = Canvas::new(size);
+        for placement in layout.positions(time) {
+            let (comment, line) = placement.item;
+            let style = animation::style(&config.animation, &placement, comment.command.size);
+            let scale = line.scale * style.scale;
+            let (left, y) = style.origin((placement.x, placement.y), (line.width, line.height));
+            for piece in &line.pieces {
+                let x = left + piece.x * style.scale;
+                match &piece.content {
+                    Content::Text(sprite) => {
+                        let position = (
+                            (x + sprite.offset.0 * style.scale).round() as i32,
+                            (y + sprite.offset.1 * style.scale).round() as i32,
+                        );
+                        if style.scale == 1.0 {
+                            canvas.draw_image(&sprite.image, position, style.alpha);
+                        } else {
+                            let (width, height) = sprite.image.dimensions();
+                            let image = imageops::resize(
+                                &sprite.image,
+                                (width as f32 * style.scale).round() as u32,
+                                (height as f32 * style.scale).round() as u32,
+                                imageops::FilterType::Triangle,
+                            );
+                            canvas.draw_image(&image, position, style.alpha);
+                        }
+                    }
+                    Content::Picture(picture, width) => {
+                        let size = ((width * style.scale).round() as u32, scale.round() as u32);
+                        let image = match scaled.get(&(picture.clone(), size.0, size.1)) {
+                            Some(image) => image,
+                            None => match pictures.get(picture) {
+                                Some(image) => scaled
+                                    .entry((picture.clone(), size.0, size.1))
+                                    .or_insert_with(|| {
+                                        imageops::resize(
+                                            image,
+                                            size.0,
+                                            size.1,
+                                            imageops::FilterType::Triangle,
+                                        )
+                                    }),
+                                None => continue,
+                            },
+                        };
+                        canvas.draw_image(image, (x.round() as i32, y.round() as i32), style.alpha);
+                    }
+                }
+            }
+        }
+
+        canvas
+            .into_image()
+            .save(out.join(format!("frame-{:06}.png", frame)))?;
+    }
+
+    Ok(())
+}
+
+/// RGBA pixels with premultiplied alpha.
+struct Canvas {
+    size: (u32, u32),
+    pixels: Vec<[f32; 4]>,
+}
+
+impl Canvas {
+    fn new(size: (u32, u32)) -> Self {
+        Canvas {
+            size,
+            pixels: vec![[0.0; 4]; (size.0 * size.1) as usize],
+        }
+    }
+
+    fn draw_image(&mut self, image: &RgbaImage, position: (i32, i32), opacity: f32) {
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let x = position.0 + x as i32;
+            let y = position.1 + y as i32;
+            if x < 0 || y < 0 || x >= self.size.0 as i32 || y >= self.size.1 as i32 {
+                continue;
+            }
+
+            let [r, g, b, a] = pixel.0;
+            let alpha = f32::from(a) / 255.0 * opacity;
+            let color = [r, g, b].map(|channel| f32::from(channel) / 255.0);
+            let target = &mut self.pixels[(y as u32 * self.size.0 + x as u32) as usize];
+            for channel in 0..3 {
+                target[channel] = color[channel] * alpha + target[channel] * (1.0 - alpha);
+            }
+            target[3] = alpha + target[3] * (1.0 - alpha);
+        }
+    }
+
+    fn into_image(self) -> RgbaImage {
+        let (width, height) = self.size;
+        let mut image = RgbaImage::new(width, height);
+        for (pixel, &[r, g, b, a]) in image.pixels_mut().zip(&self.pixels) {
+            let straight = |channel: f32| {
+                if a > 0.0 {
+                    (channel / a * 255.0).round().min(255.0) as u8
+                } else {
+                    0
+                }
+            };
+            pixel.0 = [
+                straight(r),
+                straight(g),
+                straight(b),
+                (a * 255.0).round() as u8,
+            ];
+        }
+        image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comment_alpha_scales_every_pixel() {
+        let font_set = FontSet::load(&[]);
+        let calculator = GlyphCalculatorBuilder::using_fonts(font_set.fonts().to_vec()).build();
+        let mut glyphs = calculator.cache_scope();
+        let mut pictures = Pictures::new(HashMap::new());
+        let comment = Comment::parse("faded");
+        let config = Config::default();
+        let metrics = text::Metrics::new(&config, 1080.0, 1.0);
+        let line = Line::layout(
+            &mut glyphs,
+            &font_set,
+            &mut pictures,
+            &comment,
+            &metrics,
+            &config.effects,
+            (1920.0, 1080.0),
+        );
+        let sprite = match &line.pieces[0].content {
+            Content::Text(sprite) => sprite,
+            Content::Picture(..) => unreachable!(),
+        };
+
+        let draw = |alpha| {
+            let mut canvas = Canvas::new(sprite.image.dimensions());
+            canvas.draw_image(&sprite.image, (0, 0), alpha);
+            canvas.pixels
+        };
+        let (opaque, faded) = (draw(1.0), draw(0.5));
+        assert!(opaque.iter().any(|pixel| pixel[3] == 1.0));
+        for (opaque, faded) in opaque.iter().zip(&faded) {
+            assert!((faded[3] - opaque[3] * 0.5).abs() < 1e-6);
+        }
+    }
+}
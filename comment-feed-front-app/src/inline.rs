@@ -0,0 +1,322 @@
+//! Pictures inside comments: Unicode emoji from the bundled Twemoji images, and the channel's
+//! stamps written as `:name:`.
+
+use std::{collections::HashMap, path::PathBuf};
+
+use glium_glyph::glyph_brush::GlyphCruncher;
+use image::RgbaImage;
+use log::warn;
+use twemoji_assets::png::PngTwemojiAsset;
+
+use crate::{
+    comment::Comment,
+    config::TextEffects,
+    font::FontSet,
+    text::{self, Metrics, Sprite},
+};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Picture {
+    /// The emoji sequence, as Twemoji spells it.
+    Emoji(&'static str),
+    /// A stamp name from the configuration.
+    Stamp(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Segment<'a> {
+    Text(&'a str),
+    /// A picture and the text it was written as.
+    Picture(Picture, &'a str),
+}
+
+/// Asks for the emoji presentation of the character before it.
+const VARIATION_SELECTOR: char = '\u{fe0f}';
+/// Twemoji's longest sequences are this many characters.
+const LONGEST_EMOJI: usize = 10;
+
+/// Splits `text` into text and pictures. Stamps without an image in `stamps` stay text.
+pub fn split<'a>(text: &'a str, stamps: &HashMap<String, PathBuf>) -> Vec<Segment<'a>> {
+    let mut segments = Vec::new();
+    let mut text_start = 0;
+    let mut index = 0;
+
+    while let Some(c) = text[index..].chars().next() {
+        let rest = &text[index..];
+        match stamp(rest, stamps).or_else(|| emoji(rest)) {
+            Some((picture, length)) => {
+                if text_start < index {
+                    segments.push(Segment::Text(&text[text_start..index]));
+                }
+                segments.push(Segment::Picture(picture, &rest[..length]));
+                index += length;
+                text_start = index;
+            }
+            None => index += c.len_utf8(),
+        }
+    }
+
+    if text_start < text.len() {
+        segments.push(Segment::Text(&text[text_start..]));
+    }
+    segments
+}
+
+fn stamp(text: &str, stamps: &HashMap<String, PathBuf>) -> Option<(Picture, usize)> {
+    let rest = text.strip_prefix(':')?;
+    let name = &rest[..rest.find(':')?];
+    if stamps.contains_key(name) {
+        Some((Picture::Stamp(name.to_string()), name.len() + 2))
+    } else {
+        None
+    }
+}
+
+/// The longest emoji sequence `text` starts with.
+fn emoji(text: &str) -> Option<(Picture, usize)> {
+    let first = text.chars().next()?;
+    let ends: Vec<_> = text
+        .char_indices()
+        .map(|(index, c)| index + c.len_utf8())
+        .take(LONGEST_EMOJI)
+        .collect();
+
+    ends.iter().rev().find_map(|&end| {
+        // Twemoji spells some sequences without the selectors people type, like keycaps
+        let asset = PngTwemojiAsset::from_emoji(&text[..end]).or_else(|| {
+            PngTwemojiAsset::from_emoji(&text[..end].replace(VARIATION_SELECTOR, ""))
+        })?;
+        let selected = text[end..].starts_with(VARIATION_SELECTOR);
+        // older symbols like © and ❤ are text unless they ask to be emoji
+        if end == first.len_utf8() && first < '\u{1f000}' && !selected {
+            return None;
+        }
+        let length = if selected {
+            end + VARIATION_SELECTOR.len_utf8()
+        } else {
+            end
+        };
+        Some((Picture::Emoji(asset.emoji), length))
+    })
+}
+
+/// Decoded pictures, loaded on first use.
+pub struct Pictures {
+    stamps: HashMap<String, PathBuf>,
+    loaded: HashMap<Picture, Option<RgbaImage>>,
+}
+
+impl Pictures {
+    pub fn new(stamps: HashMap<String, PathBuf>) -> Self {
+        Pictures {
+            stamps,
+            loaded: HashMap::new(),
+        }
+    }
+
+    pub fn stamps(&self) -> &HashMap<String, PathBuf> {
+        &self.stamps
+    }
+
+    /// `None` if the image can't be read, which is logged the first time.
+    pub fn get(&mut self, picture: &Picture) -> Option<&RgbaImage> {
+        let stamps = &self.stamps;
+        self.loaded
+            .entry(picture.clone())
+            .or_insert_with(|| {
+                let decoded = match picture {
+                    Picture::Emoji(emoji) => PngTwemojiAsset::from_emoji(emoji)
+                        .ok_or_else(|| "not a twemoji".to_string())
+                        .and_then(|asset| {
+                            image::load_from_memory(asset).map_err(|error| error.to_string())
+                        }),
+                    Picture::Stamp(name) => {
+                        image::open(&stamps[name]).map_err(|error| error.to_string())
+                    }
+                };
+                decoded
+                    .map(|image| image.to_rgba8())
+                    .map_err(|error| warn!("failed to load {:?}: {}", picture, error))
+                    .ok()
+            })
+            .as_ref()
+    }
+}
+
+/// A comment's text and pictures placed on one line.
+pub struct Line {
+    pub pieces: Vec<Piece>,
+    pub width: f32,
+    pub height: f32,
+    /// Font size the pieces were measured at, to draw them at the same size later.
+    pub scale: f32,
+}
+
+pub struct Piece {
+    /// From the left end of the line.
+    pub x: f32,
+    pub content: Content,
+}
+
+pub enum Content {
+    Text(Sprite),
+    /// Drawn `width` pixels wide and as high as the font size.
+    Picture(Picture, f32),
+}
+
+impl Line {
+    /// `comment` at the font size in `metrics`, with its text drawn with `effects`.
+    pub fn layout<'font, C: GlyphCruncher<'font>>(
+        cruncher: &mut C,
+        font_set: &FontSet,
+        pictures: &mut Pictures,
+        comment: &Comment,
+        metrics: &Metrics,
+        effects: &TextEffects,
+        bounds: (f32, f32),
+    ) -> Self {
+        let scale = comment.scale(metrics.font_size);
+        let mut pieces = Vec::new();
+        let mut x = 0.0;
+        let mut height: f32 = 0.0;
+
+        for segment in split(&comment.body, pictures.stamps()) {
+            let (text, picture) = match segment {
+                Segment::Text(text) => (text, None),
+                Segment::Picture(picture, text) => {
+                    let size = pictures.get(&picture).map(|image| image.dimensions());
+                    (text, size.map(|size| (picture, size)))
+                }
+            };
+
+            match picture {
+                Some((picture, (image_width, image_height))) => {
+                    let width = scale * image_width as f32 / image_height as f32;
+                    pieces.push(Piece {
+                        x,
+                        content: Content::Picture(picture, width),
+                    });
+                    x += width;
+                    height = height.max(scale);
+                }
+                // pictures that failed to load are shown as written
+                None => {
+                    let section =
+                        text::section(&font_set.runs(text), (0.0, 0.0), [1.0; 4], scale, bounds);
+                    let advance = font_set.advance(text, scale);
+                    let ink = cruncher
+                        .pixel_bounds(&section)
+                        .map_or(0.0, |rect| rect.max.y as f32);
+                    let sprite = text::sprite(
+                        cruncher.glyphs(&section),
+                        comment.color(),
+                        effects,
+                        metrics.scale_factor,
+                    );
+                    pieces.push(Piece {
+                        x,
+                        content: Content::Text(sprite),
+                    });
+                    x += advance;
+                    height = height.max(ink);
+                }
+            }
+        }
+
+        Line {
+            pieces,
+            width: x,
+            height,
+            scale,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emoji(sequence: &str) -> Picture {
+        Picture::Emoji(PngTwemojiAsset::from_emoji(sequence).unwrap().emoji)
+    }
+
+    fn stamps() -> HashMap<String, PathBuf> {
+        let mut stamps = HashMap::new();
+        stamps.insert("kusa".to_string(), PathBuf::from("kusa.png"));
+        stamps
+    }
+
+    #[test]
+    fn text_symbols_need_the_variation_selector() {
+        let none = HashMap::new();
+        assert_eq!(split("© 2021", &none), vec![Segment::Text("© 2021")]);
+        assert_eq!(split("I ❤ it", &none), vec![Segment::Text("I ❤ it")]);
+        assert_eq!(
+            split("I ❤\u{fe0f} it", &none),
+            vec![
+                Segment::Text("I "),
+                Segment::Picture(emoji("❤"), "❤\u{fe0f}"),
+                Segment::Text(" it"),
+            ]
+        );
+    }
+
+    #[test]
+    fn newer_emoji_need_no_selector() {
+        assert_eq!(
+            split("👍", &HashMap::new()),
+            vec![Segment::Picture(emoji("👍"), "👍")]
+        );
+    }
+
+    #[test]
+    fn sequences_are_matched_whole() {
+        let family = "👨\u{200d}👩\u{200d}👧";
+        assert_eq!(
+            split(&format!("a{}b", family), &HashMap::new()),
+            vec![
+                Segment::Text("a"),
+                Segment::Picture(emoji(family), family),
+                Segment::Text("b"),
+            ]
+        );
+
+        let keycap = "1\u{fe0f}\u{20e3}";
+        assert_eq!(
+            split(&format!("{}1", keycap), &HashMap::new()),
+            vec![
+                Segment::Picture(emoji("1\u{20e3}"), keycap),
+                Segment::Text("1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn typed_selectors_inside_sequences_are_allowed() {
+        let eye = "👁\u{fe0f}\u{200d}🗨\u{fe0f}";
+        assert_eq!(
+            split(eye, &HashMap::new()),
+            vec![Segment::Picture(emoji("👁\u{200d}🗨"), eye)]
+        );
+        let rainbow = "🏳\u{fe0f}\u{200d}🌈";
+        assert_eq!(
+            split(rainbow, &HashMap::new()),
+            vec![Segment::Picture(emoji(rainbow), rainbow)]
+        );
+    }
+
+    #[test]
+    fn only_configured_stamps_are_pictures() {
+        let stamps = stamps();
+        assert_eq!(
+            split(":kusa::clap:", &stamps),
+            vec![
+                Segment::Picture(Picture::Stamp("kusa".to_string()), ":kusa:"),
+                Segment::Text(":clap:"),
+            ]
+        );
+        assert_eq!(split("a : b", &stamps), vec![Segment::Text("a : b")]);
+        assert_eq!(split(":kusa", &stamps), vec![Segment::Text(":kusa")]);
+        assert_eq!(split(":", &stamps), vec![Segment::Text(":")]);
+    }
+}
@@ -1,52 +1,220 @@
+mod animation;
+mod comment;
+mod comment_log;
+mod config;
+mod control;
+mod export;
+mod font;
+mod headless;
+mod inline;
+mod quad;
+mod replay;
+mod source;
+mod text;
+
 use std::{
-    sync::mpsc::{self, Receiver, Sender},
-    thread,
-    time::Instant,
+    fs,
+    path::PathBuf,
+    sync::{mpsc, Arc},
+    time::{Duration, Instant},
 };
 
-use futures::{self, executor::spawn};
+use comment_layout::{self, Layout};
 use glium::{
     self,
-    draw_parameters::{DrawParameters, Stencil},
-    glutin::{event::Event, window::Fullscreen},
-    Surface,
-};
-use glium_glyph::{
-    self,
-    glyph_brush::{
-        rusttype::{Font, Scale},
-        Section,
+    glutin::{
+        event::{ElementState, Event, KeyboardInput, StartCause, VirtualKeyCode, WindowEvent},
+        event_loop::{ControlFlow, EventLoop},
+        window::Fullscreen,
     },
-    GlyphBrush,
+    Blend, Display, DrawParameters, Surface,
 };
+use glium_glyph::glyph_brush::GlyphCalculatorBuilder;
+use log::{info, warn};
 use overlay;
-use rand;
-use rand::Rng;
-use serde_derive::Serialize;
-
-use websocket::{self, futures::Future, ClientBuilder, Message, OwnedMessage};
-#[derive(Serialize, Clone)]
-struct SetChannelBody {
-    action: String,
-    channel: String,
-    new_channel: String,
+use simple_logger::SimpleLogger;
+use structopt::StructOpt;
+
+use comment::Comment;
+use comment_log::Recorder;
+use config::{Area, Config, MonitorSelector, Region, SourceConfig};
+use control::Control;
+use font::FontSet;
+use inline::{Content, Line, Pictures};
+use quad::{Image, Quad, QuadRenderer};
+use replay::{Playback, Replay};
+use source::{ChannelSwitch, CommentSource, Sink};
+
+#[derive(StructOpt)]
+struct Opt {
+    /// JSON configuration file
+    #[structopt(long, parse(from_os_str))]
+    config: Option<PathBuf>,
+    /// Plays a recorded comment log instead of connecting to the feed.
+    /// Space pauses, left and right arrows seek by 10 seconds while the overlay has focus
+    #[structopt(long, parse(from_os_str))]
+    replay: Option<PathBuf>,
+    /// Playback speed of --replay
+    #[structopt(long, default_value = "1.0", parse(try_from_str = positive))]
+    speed: f64,
+    /// Seconds into the --replay log to start from
+    #[structopt(long, default_value = "0")]
+    seek: f64,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+fn positive(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(number) if number.is_finite() && number > 0.0 => Ok(number),
+        Ok(_) => Err("must be above 0".to_string()),
+        Err(error) => Err(error.to_string()),
+    }
 }
 
-struct Comment {
-    body: String,
-    position: (f32, f32),
+fn at_least_one(value: &str) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(number) => Ok(number),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+#[derive(StructOpt)]
+enum Command {
+    /// Renders a recorded comment log into transparent PNG frames without opening a window
+    Render {
+        /// Comment log to render
+        #[structopt(parse(from_os_str))]
+        log: PathBuf,
+        /// Directory the frames are written to
+        #[structopt(long, parse(from_os_str))]
+        out: PathBuf,
+        #[structopt(long, default_value = "30", parse(try_from_str = at_least_one))]
+        fps: u32,
+        #[structopt(long, default_value = "1920")]
+        width: u32,
+        #[structopt(long, default_value = "1080")]
+        height: u32,
+    },
+    /// Converts a recorded comment log into a subtitle file
+    Export {
+        /// Comment log to convert
+        #[structopt(parse(from_os_str))]
+        log: PathBuf,
+        #[structopt(long, parse(from_os_str))]
+        out: PathBuf,
+        /// `ass` keeps the scrolling motion, `srt` only has the text and timing
+        #[structopt(long, default_value = "ass")]
+        format: export::Format,
+        #[structopt(long, default_value = "1920")]
+        width: u32,
+        #[structopt(long, default_value = "1080")]
+        height: u32,
+        /// Font family written into the ASS style
+        #[structopt(long, default_value = "DejaVu Sans")]
+        font: String,
+    },
+}
+
+/// Wakes the render loop from other threads.
+#[derive(Debug)]
+pub enum UserEvent {
+    /// Comments are waiting in the channel.
+    Comments,
+    Control(Control),
 }
 
 fn main() {
+    SimpleLogger::new()
+        .with_level(log::LevelFilter::Info)
+        .init()
+        .unwrap();
+
+    let opt = Opt::from_args();
+    let mut config = match &opt.config {
+        Some(path) => Config::load(path).expect("failed to load config"),
+        None => Config::default(),
+    };
+
+    match opt.command {
+        Some(Command::Render {
+            log,
+            out,
+            fps,
+            width,
+            height,
+        }) => {
+            let entries = comment_log::read(&log).expect("failed to read comment log");
+            headless::render(&config, &entries, &out, fps, (width, height))
+                .expect("failed to render");
+        }
+        Some(Command::Export {
+            log,
+            out,
+            format,
+            width,
+            height,
+            font,
+        }) => {
+            let entries = comment_log::read(&log).expect("failed to read comment log");
+            let subtitles = export::export(&config, &entries, format, (width, height), &font)
+                .expect("failed to export");
+            fs::write(&out, subtitles).expect("failed to write subtitles");
+        }
+        None => {
+            if let Some(path) = opt.replay {
+                config.source = SourceConfig::Replay {
+                    path,
+                    speed: opt.speed,
+                    seek: opt.seek,
+                };
+            }
+            run_overlay(config)
+        }
+    }
+}
+
+/// Seconds a replay moves per arrow key press.
+const SEEK_STEP: f64 = 10.0;
+/// Opacity change per up or down arrow key press.
+const OPACITY_STEP: f32 = 0.1;
+
+fn run_overlay(config: Config) {
     // 1. The **winit::EventsLoop** for handling events.
-    let events_loop = glium::glutin::event_loop::EventLoop::new();
+    let events_loop = EventLoop::with_user_event();
 
     let monitor = {
         let window = glium::glutin::window::WindowBuilder::new()
             .with_transparent(true)
             .build(&events_loop)
             .unwrap();
-        window.primary_monitor()
+        for (index, monitor) in window.available_monitors().enumerate() {
+            info!(
+                "monitor {}: {:?} {:?} at {:?}",
+                index,
+                monitor.name(),
+                monitor.size(),
+                monitor.position()
+            );
+        }
+        let selected = config.monitor.as_ref().and_then(|selector| {
+            let monitor = window
+                .available_monitors()
+                .enumerate()
+                .find(|(index, monitor)| match selector {
+                    MonitorSelector::Index(wanted) => index == wanted,
+                    MonitorSelector::Name(wanted) => monitor
+                        .name()
+                        .is_some_and(|name| name.contains(wanted.as_str())),
+                })
+                .map(|(_, monitor)| monitor);
+            if monitor.is_none() {
+                warn!("monitor {:?} not found, using the primary one", selector);
+            }
+            monitor
+        });
+        selected.or_else(|| window.primary_monitor())
     };
 
     // 2. Parameters for building the Window.
@@ -74,82 +242,271 @@ fn main() {
     }
     // later I want to use `display`
 
-    let dejavu: &[u8] = include_bytes!("../resource/fonts/DejaVuSans-2.37.ttf");
-    let fonts = vec![Font::from_bytes(dejavu).unwrap()];
-
-    let mut glyph_brush = GlyphBrush::new(&display, fonts);
+    let font_set = FontSet::load(&config.fonts);
+    let scale_factor = display.gl_window().window().scale_factor() as f32;
+    let mut metrics = screen_metrics(&display, &config, scale_factor);
+    let mut area = screen_area(&display, &config.region, scale_factor);
+    let mut params = clipped_draw_parameters(&display, area);
+    let calculator = GlyphCalculatorBuilder::using_fonts(font_set.fonts().to_vec()).build();
+    let mut pictures = Pictures::new(config.stamps.clone());
+    let mut quads = QuadRenderer::new(&display);
 
     let (msg_tx, msg_rx) = mpsc::channel();
-    web_socket(msg_tx);
-
-    let mut comments = Vec::<Comment>::new();
-
-    let mut time_last_frame = Instant::now();
-    //let window_id = display.gl_window().window().id();
-    let mut rng = rand::thread_rng();
-    let mut i = 0;
-
-    let mut target = display.draw();
-    events_loop.run(move |event, event_loop_window_target, control_flow| {
-        i += 1;
-        if (i & 4) == 0 {
-            let screen_dims = display.get_framebuffer_dimensions();
-
-            if let Ok(message) = msg_rx.try_recv() {
-                comments.push(Comment {
-                    body: message,
-                    position: (
-                        screen_dims.0 as f32,
-                        rng.gen_range(0.0..screen_dims.1 as f32),
-                    ),
-                });
+    let mut channel = None;
+    let (source, playback): (Box<dyn CommentSource>, _) = match config.source.clone() {
+        SourceConfig::Replay { path, speed, seek } => {
+            let entries = comment_log::read(&path).expect("failed to read comment log");
+            let playback = Arc::new(Playback::new(seek, speed));
+            let replay = Replay {
+                entries,
+                playback: Arc::clone(&playback),
+            };
+            (Box::new(replay), Some(playback))
+        }
+        SourceConfig::WebSocket { url, channel: name } => {
+            let switch = ChannelSwitch::new(name);
+            channel = Some(switch.clone());
+            let websocket = source::WebSocket {
+                url,
+                channel: switch,
+            };
+            (Box::new(websocket), None)
+        }
+        SourceConfig::Stdin => (Box::new(source::Stdin), None),
+        SourceConfig::UnixSocket { path } => (Box::new(source::UnixSocket { path }), None),
+        SourceConfig::TailFile { path } => (Box::new(source::TailFile { path }), None),
+    };
+    // a replay is a recording already
+    let recorder = match (&config.record_dir, &playback) {
+        (Some(dir), None) => Some(Recorder::create(dir).expect("failed to start recording")),
+        _ => None,
+    };
+    source::spawn(
+        source,
+        Sink::new(msg_tx, events_loop.create_proxy(), recorder),
+    );
+    if let Some(address) = &config.control {
+        control::listen(
+            address,
+            config.control_token.clone(),
+            events_loop.create_proxy(),
+        )
+        .expect("failed to open control socket");
+    }
+
+    let frame_interval = Duration::from_secs(1) / config.fps;
+    // replays scroll at their playback speed
+    let speed = playback.as_ref().map_or(1.0, |playback| playback.speed());
+    // seconds of comment time, which stands still while paused
+    let mut time = 0.0;
+    let mut last_frame = Instant::now();
+    let mut paused = false;
+    let mut visible = true;
+    let mut opacity = 1.0;
+    let mut layout = Layout::<(Comment, Line)>::new(text::layout_config(
+        &font_set,
+        (area.width, area.height),
+        &metrics,
+    ));
+
+    // keyboard shortcuts go through the same path as the control socket
+    let proxy = events_loop.create_proxy();
+    events_loop.run(move |event, _, control_flow| match event {
+        // a source woke us up, or the next frame is due
+        Event::UserEvent(UserEvent::Comments)
+        | Event::NewEvents(StartCause::ResumeTimeReached { .. }) => {
+            display.gl_window().window().request_redraw();
+        }
+        Event::UserEvent(UserEvent::Control(control)) => {
+            let was_paused = paused;
+            match control {
+                Control::Pause => paused = true,
+                Control::Resume => paused = false,
+                Control::TogglePause => paused = !paused,
+                Control::Clear => layout.clear(),
+                Control::Hide => visible = false,
+                Control::Show => visible = true,
+                Control::ToggleVisibility => visible = !visible,
+                Control::Opacity(value) => opacity = value.clamp(0.0, 1.0),
+                Control::OpacityBy(change) => opacity = (opacity + change).clamp(0.0, 1.0),
+                Control::Seek(seconds) => match &playback {
+                    Some(playback) => {
+                        playback.seek_by(seconds);
+                        // whatever was on screen belongs to the old position
+                        msg_rx.try_iter().for_each(drop);
+                        layout.clear();
+                    }
+                    None => warn!("only replays can seek"),
+                },
+                Control::Channel(name) => match &channel {
+                    Some(channel) => {
+                        channel.switch(name);
+                        layout.clear();
+                    }
+                    None => warn!("only the websocket source has channels"),
+                },
+            }
+            if let Some(playback) = &playback {
+                playback.set_paused(paused);
             }
+            // the time spent paused shouldn't count as one long frame
+            if was_paused && !paused {
+                last_frame = Instant::now();
+            }
+            display.gl_window().window().request_redraw();
+        }
+        Event::RedrawRequested(_) => {
+            let now = Instant::now();
+            if !paused {
+                time += (now - last_frame).as_secs_f64() * speed;
+            }
+            last_frame = now;
+            let bounds = (area.width, area.height);
+            let mut glyphs = calculator.cache_scope();
+
+            for message in msg_rx.try_iter() {
+                let comment = Comment::parse(&message);
+                let position = comment.command.position;
+                let line = Line::layout(
+                    &mut glyphs,
+                    &font_set,
+                    &mut pictures,
+                    &comment,
+                    &metrics,
+                    &config.effects,
+                    bounds,
+                );
+                let (width, height) = (line.width, line.height);
+                layout.place((comment, line), position, width, height, time);
+            }
+
+            layout.expire(time);
 
-            let time_current_frame = Instant::now();
-            comments.iter_mut().for_each(|comment| {
-                comment.position.0 -=
-                    100.0 * (time_current_frame - time_last_frame).as_secs_f32() as f32;
-                glyph_brush.queue(Section {
-                    text: &comment.body,
-                    bounds: (screen_dims.0 as f32, screen_dims.1 as f32),
-                    color: [1.0, 1.0, 1.0, 1.0],
-                    screen_position: comment.position,
-                    scale: Scale::uniform(50.0),
-                    ..Section::default()
-                });
-            });
-            time_last_frame = Instant::now();
+            // hidden comments keep moving, they just aren't drawn
+            for placement in layout.positions(time).filter(|_| visible) {
+                let (comment, line) = placement.item;
+                let style = animation::style(&config.animation, &placement, comment.command.size);
+                let alpha = opacity * style.alpha;
+                let scale = line.scale * style.scale;
+                let (left, y) = style.origin(
+                    (area.x + placement.x, area.y + placement.y),
+                    (line.width, line.height),
+                );
+                for piece in &line.pieces {
+                    let x = left + piece.x * style.scale;
+                    match &piece.content {
+                        Content::Text(sprite) => {
+                            quads.upload(&display, sprite);
+                            let (width, height) = sprite.image.dimensions();
+                            quads.queue(Quad {
+                                image: Image::Sprite(sprite.id),
+                                position: (
+                                    x + sprite.offset.0 * style.scale,
+                                    y + sprite.offset.1 * style.scale,
+                                ),
+                                size: (width as f32 * style.scale, height as f32 * style.scale),
+                                alpha,
+                            });
+                        }
+                        Content::Picture(picture, width) => quads.queue(Quad {
+                            image: Image::Picture(picture.clone()),
+                            position: (x, y),
+                            size: (width * style.scale, scale),
+                            alpha,
+                        }),
+                    }
+                }
+            }
 
             let mut target = display.draw();
             target.clear_color_and_depth((0.0, 0.0, 0.0, 0.0), 0.0);
-            glyph_brush.draw_queued(&display, &mut target);
+            quads.draw_queued(&display, &mut pictures, &mut target, &params);
             target.finish().unwrap();
+
+            // keep animating while something is on screen, otherwise sleep until a comment arrives
+            *control_flow = if layout.is_empty() || paused {
+                ControlFlow::Wait
+            } else {
+                ControlFlow::WaitUntil(now + frame_interval)
+            };
+        }
+        // moving to a monitor with another scale factor resizes the window too
+        Event::WindowEvent {
+            event: WindowEvent::Resized(_),
+            ..
+        }
+        | Event::WindowEvent {
+            event: WindowEvent::ScaleFactorChanged { .. },
+            ..
+        } => {
+            let scale_factor = display.gl_window().window().scale_factor() as f32;
+            metrics = screen_metrics(&display, &config, scale_factor);
+            area = screen_area(&display, &config.region, scale_factor);
+            params = clipped_draw_parameters(&display, area);
+            // comments already on screen keep their size, new ones get the new one
+            layout.set_config(text::layout_config(
+                &font_set,
+                (area.width, area.height),
+                &metrics,
+            ));
+        }
+        Event::WindowEvent {
+            event:
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(key),
+                            ..
+                        },
+                    ..
+                },
+            ..
+        } => {
+            let control = match key {
+                VirtualKeyCode::Space => Control::TogglePause,
+                VirtualKeyCode::C => Control::Clear,
+                VirtualKeyCode::H => Control::ToggleVisibility,
+                VirtualKeyCode::Up => Control::OpacityBy(OPACITY_STEP),
+                VirtualKeyCode::Down => Control::OpacityBy(-OPACITY_STEP),
+                VirtualKeyCode::Left if playback.is_some() => Control::Seek(-SEEK_STEP),
+                VirtualKeyCode::Right if playback.is_some() => Control::Seek(SEEK_STEP),
+                _ => return,
+            };
+            let _ = proxy.send_event(UserEvent::Control(control));
         }
+        Event::WindowEvent {
+            event: WindowEvent::CloseRequested,
+            ..
+        } => {
+            *control_flow = ControlFlow::Exit;
+        }
+        _ => (),
     });
 }
 
-fn web_socket(message_sender: Sender<String>) {
-    thread::spawn(move || {
-        let mut client = ClientBuilder::new(
-            "wss://7ht6ij8i09.execute-api.ap-northeast-1.amazonaws.com/production",
-        )
-        .unwrap()
-        .connect_secure(None)
-        .unwrap();
+fn screen_area(display: &Display, region: &Region, scale_factor: f32) -> Area {
+    let (width, height) = display.get_framebuffer_dimensions();
+    region.resolve((width as f32, height as f32), scale_factor)
+}
 
-        let mut messages = client.incoming_messages();
+fn screen_metrics(display: &Display, config: &Config, scale_factor: f32) -> text::Metrics {
+    let (_, height) = display.get_framebuffer_dimensions();
+    text::Metrics::new(config, height as f32, scale_factor)
+}
 
-        while let Some(Ok(message)) = messages.next() {
-            dbg!("rrrrrr");
-            match message {
-                OwnedMessage::Text(message) => {
-                    dbg!(message.clone());
-                    message_sender.send(message).unwrap();
-                }
-                _ => {
-                    dbg!(message);
-                }
-            }
-        }
-    });
+/// Blending for text and pictures, only drawing inside `area`.
+fn clipped_draw_parameters(display: &Display, area: Area) -> DrawParameters<'static> {
+    let (_, screen_height) = display.get_framebuffer_dimensions();
+    DrawParameters {
+        blend: Blend::alpha_blending(),
+        // glium counts from the bottom
+        scissor: Some(glium::Rect {
+            left: area.x as u32,
+            bottom: screen_height - (area.y + area.height) as u32,
+            width: area.width as u32,
+            height: area.height as u32,
+        }),
+        ..DrawParameters::default()
+    }
 }
@@ -0,0 +1,147 @@
+//! Draws comments as textured quads: their text, drawn beforehand on the CPU, and pictures.
+
+use std::collections::{HashMap, HashSet};
+
+use glium::{
+    implement_vertex, index::NoIndices, index::PrimitiveType, program::ProgramCreationInput,
+    texture::RawImage2d, uniform, Display, DrawParameters, Frame, Program, Surface, Texture2d,
+    VertexBuffer,
+};
+
+use crate::{
+    inline::{Picture, Pictures},
+    text::Sprite,
+};
+
+#[derive(Clone, Copy)]
+struct Vertex {
+    a_pos: [f32; 2],
+    a_uv: [f32; 2],
+}
+
+implement_vertex!(Vertex, a_pos, a_uv);
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum Image {
+    Picture(Picture),
+    /// By [`Sprite::id`], uploaded with [`QuadRenderer::upload`].
+    Sprite(u64),
+}
+
+/// An image to draw, in pixels from the top left corner of the screen.
+pub struct Quad {
+    pub image: Image,
+    pub position: (f32, f32),
+    pub size: (f32, f32),
+    pub alpha: f32,
+}
+
+pub struct QuadRenderer {
+    program: Program,
+    /// `None` for pictures that failed to load, so they aren't tried every frame.
+    textures: HashMap<Image, Option<Texture2d>>,
+    queue: Vec<Quad>,
+}
+
+impl QuadRenderer {
+    pub fn new(display: &Display) -> Self {
+        let program = Program::new(
+            display,
+            ProgramCreationInput::SourceCode {
+                vertex_shader: include_str!("shader.vert"),
+                fragment_shader: include_str!("shader.frag"),
+                tessellation_control_shader: None,
+                tessellation_evaluation_shader: None,
+                geometry_shader: None,
+                transform_feedback_varyings: None,
+                // the images are sRGB already, draw them as they are like the text
+                outputs_srgb: true,
+                uses_point_size: false,
+            },
+        )
+        .expect("failed to compile the picture shaders");
+
+        QuadRenderer {
+            program,
+            textures: HashMap::new(),
+            queue: Vec::new(),
+        }
+    }
+
+    pub fn queue(&mut self, quad: Quad) {
+        self.queue.push(quad);
+    }
+
+    /// Makes `sprite` available as [`Image::Sprite`] for as long as it is drawn every frame.
+    pub fn upload(&mut self, display: &Display, sprite: &Sprite) {
+        self.textures
+            .entry(Image::Sprite(sprite.id))
+            .or_insert_with(|| {
+                let image = RawImage2d::from_raw_rgba_reversed(
+                    sprite.image.as_raw(),
+                    sprite.image.dimensions(),
+                );
+                Texture2d::new(display, image).ok()
+            });
+    }
+
+    /// Draws and forgets every queued quad.
+    pub fn draw_queued(
+        &mut self,
+        display: &Display,
+        pictures: &mut Pictures,
+        frame: &mut Frame,
+        params: &DrawParameters,
+    ) {
+        let (width, height) = frame.get_dimensions();
+        let screen = [width as f32, height as f32];
+        let mut drawn = HashSet::new();
+
+        for quad in self.queue.drain(..) {
+            let texture = self.textures.entry(quad.image.clone()).or_insert_with(|| {
+                let image = match &quad.image {
+                    Image::Picture(picture) => pictures.get(picture)?,
+                    Image::Sprite(_) => return None,
+                };
+                let image = RawImage2d::from_raw_rgba_reversed(image.as_raw(), image.dimensions());
+                Texture2d::new(display, image).ok()
+            });
+            let texture = match texture {
+                Some(texture) => texture,
+                None => continue,
+            };
+
+            let (x, y) = quad.position;
+            let (w, h) = quad.size;
+            let vertex = |a_pos, a_uv| Vertex { a_pos, a_uv };
+            let vertices = [
+                vertex([x, y], [0.0, 1.0]),
+                vertex([x + w, y], [1.0, 1.0]),
+                vertex([x, y + h], [0.0, 0.0]),
+                vertex([x + w, y + h], [1.0, 0.0]),
+            ];
+            let vertices = VertexBuffer::new(display, &vertices).unwrap();
+
+            frame
+                .draw(
+                    &vertices,
+                    NoIndices(PrimitiveType::TriangleStrip),
+                    &self.program,
+                    &uniform! {
+                        u_screen: screen,
+                        u_texture: texture.sampled(),
+                        u_alpha: quad.alpha,
+                    },
+                    params,
+                )
+                .unwrap();
+            drawn.insert(quad.image);
+        }
+
+        // the comments of sprites that weren't drawn have left the screen
+        self.textures.retain(|image, _| match image {
+            Image::Picture(_) => true,
+            Image::Sprite(_) => drawn.contains(image),
+        });
+    }
+}
@@ -0,0 +1,118 @@
+//! Feeds a recorded comment log into the overlay with its original timing.
+
+use std::{
+    error::Error,
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use log::info;
+
+use crate::{
+    comment_log::Entry,
+    source::{CommentSource, Sink},
+};
+
+/// Position in the log, shared between the replay thread and whoever controls it.
+pub struct Playback {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+struct State {
+    /// Log time at `anchor`.
+    position: f64,
+    anchor: Instant,
+    speed: f64,
+    paused: bool,
+    /// Bumped on every seek, so the replay thread knows to look up its next entry again.
+    seeks: u64,
+}
+
+impl State {
+    fn position(&self) -> f64 {
+        if self.paused {
+            self.position
+        } else {
+            self.position + self.anchor.elapsed().as_secs_f64() * self.speed
+        }
+    }
+}
+
+impl Playback {
+    pub fn new(position: f64, speed: f64) -> Self {
+        Playback {
+            state: Mutex::new(State {
+                position,
+                anchor: Instant::now(),
+                speed,
+                paused: false,
+                seeks: 0,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.state.lock().unwrap().speed
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.position = state.position();
+        state.anchor = Instant::now();
+        state.paused = paused;
+        self.changed.notify_all();
+    }
+
+    /// Moves the position by `seconds`, which may be negative. Entries skipped over are not sent.
+    ///
+    /// Once this returns, nothing from before the seek will be sent anymore.
+    pub fn seek_by(&self, seconds: f64) {
+        let mut state = self.state.lock().unwrap();
+        state.position = (state.position() + seconds).max(0.0);
+        state.anchor = Instant::now();
+        state.seeks += 1;
+        info!("replay seeked to {:.1}s", state.position);
+        self.changed.notify_all();
+    }
+}
+
+/// Sends the entries of a log when playback reaches them.
+pub struct Replay {
+    pub entries: Vec<Entry>,
+    pub playback: Arc<Playback>,
+}
+
+impl CommentSource for Replay {
+    fn run(self: Box<Self>, sink: Sink) -> Result<(), Box<dyn Error>> {
+        let Replay { entries, playback } = *self;
+        let mut state = playback.state.lock().unwrap();
+        let mut seeks = None;
+        let mut next = 0;
+
+        loop {
+            if seeks != Some(state.seeks) {
+                seeks = Some(state.seeks);
+                let position = state.position();
+                next = entries.partition_point(|entry| entry.elapsed < position);
+            }
+
+            if state.paused || next == entries.len() {
+                state = playback.changed.wait(state).unwrap();
+                continue;
+            }
+
+            let ahead = entries[next].elapsed - state.position();
+            if ahead > 0.0 {
+                let timeout = Duration::from_secs_f64(ahead / state.speed);
+                state = playback.changed.wait_timeout(state, timeout).unwrap().0;
+                continue;
+            }
+
+            // sent while holding the lock, so that a seek can't interleave
+            sink.send(entries[next].message.clone())?;
+            next += 1;
+        }
+    }
+}
@@ -1,12 +1,12 @@
-#version 450
-#extension GL_ARB_separate_shader_objects : enable
+#version 140
 
-layout(location = 0) in vec2 v_uv;
-layout(location = 0) out vec4 target0;
+in vec2 v_uv;
+out vec4 target0;
 
-layout(set = 0, binding = 0) uniform texture2D u_texture;
-layout(set = 0, binding = 1) uniform sampler u_sampler;
+uniform sampler2D u_texture;
+uniform float u_alpha;
 
 void main() {
-    target0 = texture(sampler2D(u_texture, u_sampler), v_uv);
-}
\ No newline at end of file
+    vec4 color = texture(u_texture, v_uv);
+    target0 = vec4(color.rgb, color.a * u_alpha);
+}
@@ -1,18 +1,14 @@
-#version 450
-#extension GL_ARB_separate_shader_objects : enable
+#version 140
 
-// see colour-uniform example for the case where this is a push constant
-const float scale = 1.2f;
+// pixels from the top left corner of the screen
+in vec2 a_pos;
+in vec2 a_uv;
+out vec2 v_uv;
 
-layout(location = 0) in vec2 a_pos;
-layout(location = 1) in vec2 a_uv;
-layout(location = 0) out vec2 v_uv;
-
-out gl_PerVertex {
-    vec4 gl_Position;
-};
+uniform vec2 u_screen;
 
 void main() {
     v_uv = a_uv;
-    gl_Position = vec4(scale * a_pos, 0.0, 1.0);
-}
\ No newline at end of file
+    vec2 position = a_pos / u_screen * 2.0 - 1.0;
+    gl_Position = vec4(position.x, -position.y, 0.0, 1.0);
+}
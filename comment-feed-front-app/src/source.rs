@@ -0,0 +1,283 @@
+//! Where comments come from. Every source runs on its own thread and hands each message, plain
+//! text or a JSON payload, to a [`Sink`] feeding the render loop.
+
+use std::{
+    error::Error,
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, Seek, SeekFrom},
+    net::{Shutdown, TcpStream},
+    path::PathBuf,
+    sync::{mpsc::Sender, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use glium::glutin::event_loop::EventLoopProxy;
+use log::{debug, error, info, warn};
+use serde_derive::Serialize;
+use websocket::{stream::sync::AsTcpStream, ClientBuilder, OwnedMessage};
+
+use crate::{comment_log::Recorder, UserEvent};
+
+pub trait CommentSource: Send {
+    /// Pushes messages into `sink` until the source runs dry or the overlay goes away.
+    fn run(self: Box<Self>, sink: Sink) -> Result<(), Box<dyn Error>>;
+}
+
+pub fn spawn(source: Box<dyn CommentSource>, sink: Sink) {
+    thread::spawn(move || match source.run(sink) {
+        Ok(()) => info!("comment source finished"),
+        Err(error) => error!("comment source failed: {}", error),
+    });
+}
+
+/// Hands messages to the render loop, recording them on the way if asked to.
+#[derive(Clone)]
+pub struct Sink {
+    sender: Sender<String>,
+    wake_up: EventLoopProxy<UserEvent>,
+    recorder: Option<Arc<Mutex<Recorder>>>,
+}
+
+/// The render loop has gone away.
+#[derive(Debug)]
+pub struct Closed;
+
+impl fmt::Display for Closed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "overlay closed")
+    }
+}
+
+impl Error for Closed {}
+
+impl Sink {
+    pub fn new(
+        sender: Sender<String>,
+        wake_up: EventLoopProxy<UserEvent>,
+        recorder: Option<Recorder>,
+    ) -> Self {
+        Sink {
+            sender,
+            wake_up,
+            recorder: recorder.map(|recorder| Arc::new(Mutex::new(recorder))),
+        }
+    }
+
+    pub fn send(&self, message: String) -> Result<(), Closed> {
+        info!("received {:?}", message);
+        if let Some(recorder) = &self.recorder {
+            if let Err(error) = recorder.lock().unwrap().record(&message) {
+                warn!("failed to record comment: {}", error);
+            }
+        }
+        self.sender.send(message).map_err(|_| Closed)?;
+        self.wake_up
+            .send_event(UserEvent::Comments)
+            .map_err(|_| Closed)
+    }
+}
+
+/// The comment feed's API Gateway endpoint, or any websocket sending text frames.
+pub struct WebSocket {
+    pub url: String,
+    pub channel: ChannelSwitch,
+}
+
+/// Channel the websocket source listens to, switchable while it runs.
+#[derive(Clone)]
+pub struct ChannelSwitch {
+    state: Arc<Mutex<ChannelState>>,
+}
+
+struct ChannelState {
+    channel: String,
+    /// The connection listening to `channel`, if any.
+    stream: Option<TcpStream>,
+}
+
+/// Moves a connection from one channel to another. The backend puts new connections in `test`.
+#[derive(Serialize, Clone)]
+struct SetChannelBody {
+    action: String,
+    channel: String,
+    new_channel: String,
+}
+
+/// The channel every connection starts in.
+const INITIAL_CHANNEL: &str = "test";
+
+impl ChannelSwitch {
+    pub fn new(channel: String) -> Self {
+        ChannelSwitch {
+            state: Arc::new(Mutex::new(ChannelState {
+                channel,
+                stream: None,
+            })),
+        }
+    }
+
+    /// Drops the current connection so the source reconnects to `channel`.
+    pub fn switch(&self, channel: String) {
+        let mut state = self.state.lock().unwrap();
+        state.channel = channel;
+        if let Some(stream) = state.stream.take() {
+            // unblocks the reading thread
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    fn current(&self) -> String {
+        self.state.lock().unwrap().channel.clone()
+    }
+}
+
+impl CommentSource for WebSocket {
+    fn run(self: Box<Self>, sink: Sink) -> Result<(), Box<dyn Error>> {
+        loop {
+            let channel = self.channel.current();
+            let mut client = ClientBuilder::new(&self.url)?.connect(None)?;
+            info!("connected to {}", self.url);
+
+            if channel != INITIAL_CHANNEL {
+                let body = SetChannelBody {
+                    action: "setchannel".to_string(),
+                    channel: INITIAL_CHANNEL.to_string(),
+                    new_channel: channel.clone(),
+                };
+                client.send_message(&OwnedMessage::Text(serde_json::to_string(&body)?))?;
+            }
+            info!("listening to channel {}", channel);
+
+            {
+                let mut state = self.channel.state.lock().unwrap();
+                // switched while connecting
+                if state.channel != channel {
+                    continue;
+                }
+                state.stream = Some(client.stream_ref().as_tcp().try_clone()?);
+            }
+
+            let result = (|| {
+                for message in client.incoming_messages() {
+                    match message? {
+                        OwnedMessage::Text(message) => sink.send(message)?,
+                        OwnedMessage::Close(_) => break,
+                        message => debug!("ignored {:?}", message),
+                    }
+                }
+                Ok(())
+            })();
+
+            // the connection was dropped on purpose, the new channel gets a new one
+            if self.channel.current() != channel {
+                continue;
+            }
+            return result;
+        }
+    }
+}
+
+/// One message per line on standard input.
+pub struct Stdin;
+
+impl CommentSource for Stdin {
+    fn run(self: Box<Self>, sink: Sink) -> Result<(), Box<dyn Error>> {
+        let stdin = io::stdin();
+        send_lines(stdin.lock(), &sink)
+    }
+}
+
+/// A Unix domain socket accepting any number of clients, each writing one message per line.
+pub struct UnixSocket {
+    pub path: PathBuf,
+}
+
+impl CommentSource for UnixSocket {
+    #[cfg(unix)]
+    fn run(self: Box<Self>, sink: Sink) -> Result<(), Box<dyn Error>> {
+        use std::{
+            fs,
+            os::unix::{fs::FileTypeExt, net::UnixListener},
+        };
+
+        // a socket file left behind by an earlier run would make bind fail, anything else there
+        // is somebody's file
+        if let Ok(metadata) = fs::symlink_metadata(&self.path) {
+            if !metadata.file_type().is_socket() {
+                return Err(format!("{:?} exists and is not a socket", self.path).into());
+            }
+            fs::remove_file(&self.path)?;
+        }
+        let listener = UnixListener::bind(&self.path)?;
+        info!("listening on {:?}", self.path);
+
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let sink = sink.clone();
+            thread::spawn(move || {
+                if let Err(error) = send_lines(BufReader::new(stream), &sink) {
+                    warn!("unix socket client failed: {}", error);
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn run(self: Box<Self>, _sink: Sink) -> Result<(), Box<dyn Error>> {
+        Err("unix sockets are not supported on this platform".into())
+    }
+}
+
+/// Follows a file like `tail -f`, one message per line. Only lines added after start are read.
+pub struct TailFile {
+    pub path: PathBuf,
+}
+
+const TAIL_INTERVAL: Duration = Duration::from_millis(200);
+
+impl CommentSource for TailFile {
+    fn run(self: Box<Self>, sink: Sink) -> Result<(), Box<dyn Error>> {
+        let mut file = BufReader::new(File::open(&self.path)?);
+        let mut position = file.seek(SeekFrom::End(0))?;
+        let mut line = String::new();
+        info!("following {:?}", self.path);
+
+        loop {
+            let read = file.read_line(&mut line)?;
+            position += read as u64;
+
+            if line.ends_with('\n') {
+                let message = line.trim_end_matches(&['\r', '\n'][..]);
+                if !message.is_empty() {
+                    sink.send(message.to_string())?;
+                }
+                line.clear();
+                continue;
+            }
+
+            // at the end for now, keep any partial line until the rest of it is written
+            if read == 0 {
+                if file.get_ref().metadata()?.len() < position {
+                    info!("{:?} was truncated, reading from the start", self.path);
+                    position = file.seek(SeekFrom::Start(0))?;
+                    line.clear();
+                }
+                thread::sleep(TAIL_INTERVAL);
+            }
+        }
+    }
+}
+
+fn send_lines<R: BufRead>(reader: R, sink: &Sink) -> Result<(), Box<dyn Error>> {
+    for line in reader.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            sink.send(line)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,329 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use comment_layout::{FIXED_DURATION, SCROLL_SPEED};
+use glium_glyph::glyph_brush::{
+    rusttype::{PositionedGlyph, Scale},
+    FontId, GlyphCruncher, SectionText, VariedSection,
+};
+use image::RgbaImage;
+
+use crate::{
+    config::{Config, TextEffects},
+    font::FontSet,
+};
+
+/// Sizes in physical pixels for the screen being drawn on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Metrics {
+    /// Font size of `medium` comments.
+    pub font_size: f32,
+    /// Pixels per second.
+    pub speed: f32,
+    /// Physical pixels per logical pixel.
+    pub scale_factor: f32,
+}
+
+impl Metrics {
+    pub fn new(config: &Config, screen_height: f32, scale_factor: f32) -> Self {
+        Metrics {
+            font_size: config.font_size.resolve(screen_height, scale_factor),
+            speed: SCROLL_SPEED * scale_factor,
+            scale_factor,
+        }
+    }
+}
+
+/// Tells sprites apart, for the textures made of them.
+static NEXT_SPRITE: AtomicU64 = AtomicU64::new(0);
+
+/// A piece of text drawn once together with its shadow and outline, so that fading it fades
+/// everything evenly instead of building up where copies overlap.
+pub struct Sprite {
+    pub id: u64,
+    /// Straight alpha, at full opacity.
+    pub image: RgbaImage,
+    /// Top left corner of `image` from where the text is placed, as the effects reach past it.
+    pub offset: (f32, f32),
+}
+
+/// Draws `glyphs` in `color` over their outline and shadow. Effect sizes are in logical pixels.
+///
+/// The outline spreads the text's coverage by its width, so it is exactly as opaque as its
+/// color wherever it shows.
+pub fn sprite<'a, 'font: 'a>(
+    glyphs: impl IntoIterator<Item = &'a PositionedGlyph<'font>>,
+    color: [f32; 4],
+    effects: &TextEffects,
+    scale_factor: f32,
+) -> Sprite {
+    let glyphs: Vec<_> = glyphs
+        .into_iter()
+        .filter_map(|glyph| Some((glyph, glyph.pixel_bounding_box()?)))
+        .collect();
+    let outline = effects.outline.filter(|outline| outline.width > 0.0);
+    let radius = outline.map_or(0.0, |outline| outline.width * scale_factor);
+    let shadow = effects.shadow.map(|shadow| {
+        let offset = (
+            (shadow.offset.0 * scale_factor).round() as i32,
+            (shadow.offset.1 * scale_factor).round() as i32,
+        );
+        (shadow.color, offset)
+    });
+    let reach = radius.ceil() as i32 + shadow.map_or(0, |(_, (x, y))| x.abs().max(y.abs()));
+
+    let (min, max) = glyphs
+        .iter()
+        .map(|(_, bounds)| ((bounds.min.x, bounds.min.y), (bounds.max.x, bounds.max.y)))
+        .reduce(|(min, max), (glyph_min, glyph_max)| {
+            (
+                (min.0.min(glyph_min.0), min.1.min(glyph_min.1)),
+                (max.0.max(glyph_max.0), max.1.max(glyph_max.1)),
+            )
+        })
+        .unwrap_or(((0, 0), (0, 0)));
+    let origin = (min.0 - reach, min.1 - reach);
+    let mut fill = Mask::new(
+        (max.0 - min.0 + 2 * reach).max(1) as u32,
+        (max.1 - min.1 + 2 * reach).max(1) as u32,
+    );
+    for (glyph, bounds) in &glyphs {
+        glyph.draw(|x, y, coverage| {
+            let x = bounds.min.x + x as i32 - origin.0;
+            let y = bounds.min.y + y as i32 - origin.1;
+            let value = fill.get(x, y).max(coverage);
+            fill.set(x, y, value);
+        });
+    }
+
+    let mut layers = Vec::new();
+    if let Some((shadow_color, offset)) = shadow {
+        layers.push((shadow_color, fill.shifted(offset)));
+    }
+    if let Some(outline) = outline {
+        layers.push((outline.color, fill.dilated(radius)));
+    }
+    layers.push((color, fill));
+
+    let (width, height) = (layers[0].1.width, layers[0].1.height);
+    let mut image = RgbaImage::new(width, height);
+    for (index, pixel) in image.pixels_mut().enumerate() {
+        // premultiplied, back to front
+        let mut blended = [0.0; 4];
+        for (color, mask) in &layers {
+            let alpha = color[3] * mask.values[index];
+            for channel in 0..3 {
+                blended[channel] = color[channel] * alpha + blended[channel] * (1.0 - alpha);
+            }
+            blended[3] = alpha + blended[3] * (1.0 - alpha);
+        }
+        let [r, g, b, a] = blended;
+        let straight = |channel: f32| {
+            if a > 0.0 {
+                (channel / a * 255.0).round().min(255.0) as u8
+            } else {
+                0
+            }
+        };
+        pixel.0 = [
+            straight(r),
+            straight(g),
+            straight(b),
+            (a * 255.0).round() as u8,
+        ];
+    }
+
+    Sprite {
+        id: NEXT_SPRITE.fetch_add(1, Ordering::Relaxed),
+        image,
+        offset: (origin.0 as f32, origin.1 as f32),
+    }
+}
+
+/// Coverage from 0 to 1 for each pixel.
+struct Mask {
+    width: u32,
+    height: u32,
+    values: Vec<f32>,
+}
+
+impl Mask {
+    fn new(width: u32, height: u32) -> Self {
+        Mask {
+            width,
+            height,
+            values: vec![0.0; (width * height) as usize],
+        }
+    }
+
+    /// 0 outside the mask.
+    fn get(&self, x: i32, y: i32) -> f32 {
+        self.index(x, y).map_or(0.0, |index| self.values[index])
+    }
+
+    fn set(&mut self, x: i32, y: i32, value: f32) {
+        if let Some(index) = self.index(x, y) {
+            self.values[index] = value;
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            None
+        } else {
+            Some((y as u32 * self.width + x as u32) as usize)
+        }
+    }
+
+    fn map(&self, value: impl Fn(i32, i32) -> f32) -> Self {
+        let mut mapped = Mask::new(self.width, self.height);
+        for y in 0..self.height as i32 {
+            for x in 0..self.width as i32 {
+                mapped.set(x, y, value(x, y));
+            }
+        }
+        mapped
+    }
+
+    fn shifted(&self, (dx, dy): (i32, i32)) -> Self {
+        self.map(|x, y| self.get(x - dx, y - dy))
+    }
+
+    /// Every pixel takes the most covered one within `radius`, fading out over the last pixel.
+    fn dilated(&self, radius: f32) -> Self {
+        let reach = radius.ceil() as i32;
+        let mut kernel = Vec::new();
+        for dy in -reach..=reach {
+            for dx in -reach..=reach {
+                let distance = ((dx * dx + dy * dy) as f32).sqrt();
+                let weight = (radius + 0.5 - distance).clamp(0.0, 1.0);
+                if weight > 0.0 {
+                    kernel.push((dx, dy, weight));
+                }
+            }
+        }
+        self.map(|x, y| {
+            kernel
+                .iter()
+                .map(|&(dx, dy, weight)| self.get(x + dx, y + dy) * weight)
+                .fold(0.0, f32::max)
+        })
+    }
+}
+
+/// Lanes one line of text high, over an area of `size` pixels.
+pub fn layout_config(
+    font_set: &FontSet,
+    size: (f32, f32),
+    metrics: &Metrics,
+) -> comment_layout::Config {
+    let v_metrics = font_set.fonts()[0].v_metrics(Scale::uniform(metrics.font_size));
+    comment_layout::Config {
+        width: size.0,
+        height: size.1,
+        lane_height: v_metrics.ascent - v_metrics.descent + v_metrics.line_gap,
+        speed: metrics.speed,
+        fixed_duration: FIXED_DURATION,
+    }
+}
+
+/// Width and height of `text` as laid out by [`section`].
+pub fn measure<'font, C: GlyphCruncher<'font>>(
+    cruncher: &mut C,
+    font_set: &FontSet,
+    text: &str,
+    scale: f32,
+    bounds: (f32, f32),
+) -> (f32, f32) {
+    cruncher
+        .pixel_bounds(section(
+            &font_set.runs(text),
+            (0.0, 0.0),
+            [1.0, 1.0, 1.0, 1.0],
+            scale,
+            bounds,
+        ))
+        .map_or((0.0, 0.0), |rect| (rect.max.x as f32, rect.max.y as f32))
+}
+
+pub fn section<'a>(
+    runs: &[(&'a str, FontId)],
+    screen_position: (f32, f32),
+    color: [f32; 4],
+    scale: f32,
+    bounds: (f32, f32),
+) -> VariedSection<'a> {
+    VariedSection {
+        text: runs
+            .iter()
+            .map(|&(text, font_id)| SectionText {
+                text,
+                scale: Scale::uniform(scale),
+                color,
+                font_id,
+            })
+            .collect(),
+        bounds,
+        screen_position,
+        ..VariedSection::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glium_glyph::glyph_brush::GlyphCalculatorBuilder;
+
+    use super::*;
+    use crate::config::Outline;
+
+    fn sprite_of(text: &str, effects: &TextEffects) -> Sprite {
+        let font_set = FontSet::load(&[]);
+        let calculator = GlyphCalculatorBuilder::using_fonts(font_set.fonts().to_vec()).build();
+        let mut glyphs = calculator.cache_scope();
+        let section = section(&font_set.runs(text), (0.0, 0.0), [1.0; 4], 40.0, (1e3, 1e3));
+        sprite(glyphs.glyphs(&section), [1.0; 4], effects, 2.0)
+    }
+
+    #[test]
+    fn outlines_are_as_opaque_as_their_color() {
+        let effects = TextEffects {
+            outline: Some(Outline {
+                width: 2.0,
+                color: [0.0, 0.0, 0.0, 0.5],
+            }),
+            shadow: None,
+        };
+        let sprite = sprite_of("WiW", &effects);
+        // black pixels are outline only, white text covers the rest
+        let outline: Vec<u8> = sprite
+            .image
+            .pixels()
+            .filter(|pixel| pixel.0[..3] == [0, 0, 0])
+            .map(|pixel| pixel.0[3])
+            .collect();
+        assert_eq!(outline.iter().max(), Some(&128));
+        assert!(sprite.image.pixels().any(|pixel| pixel.0 == [255; 4]));
+    }
+
+    #[test]
+    fn sprites_leave_room_for_the_outline() {
+        let effects = TextEffects {
+            outline: Some(Outline {
+                width: 2.0,
+                color: [0.0, 0.0, 0.0, 1.0],
+            }),
+            shadow: None,
+        };
+        let plain = sprite_of(
+            "W",
+            &TextEffects {
+                outline: None,
+                shadow: None,
+            },
+        );
+        let outlined = sprite_of("W", &effects);
+        // 2 logical pixels at a scale factor of 2 on every side
+        assert_eq!(outlined.image.width(), plain.image.width() + 8);
+        assert_eq!(outlined.offset.0, plain.offset.0 - 4.0);
+        assert_ne!(outlined.id, plain.id);
+    }
+}
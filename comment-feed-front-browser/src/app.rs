@@ -1,4 +1,5 @@
 use chrono::{DateTime, Local, NaiveDateTime, NaiveTime};
+use comment_layout::command::{Color, Command, Position, Size};
 use futures::{prelude::*, stream::StreamFuture, StreamExt};
 use js_sys::JsString;
 use log::*;
@@ -12,7 +13,7 @@ use std::{
         Arc,
     },
     task::Poll,
-    time::SystemTime,
+    time::{Duration, SystemTime},
 };
 use strum::IntoEnumIterator;
 use tokio;
@@ -22,16 +23,42 @@ use web_sys::MessageEvent;
 use ws_stream_wasm::*;
 use yew::format::Json;
 use yew::prelude::*;
+use yew::services::render::{RenderService, RenderTask};
 use yew::services::storage::{Area, StorageService};
+use yew::services::timeout::{TimeoutService, TimeoutTask};
 
-const KEY: &str = "yew.todomvc.self";
+use crate::{
+    filter::{self, Filter},
+    history::History,
+    list::{self, List},
+    preview::{self, Preview},
+    route::{self, Page},
+    search::{self, Search},
+};
+
+const ENDPOINT: &str = "wss://7ht6ij8i09.execute-api.ap-northeast-1.amazonaws.com/production";
+/// The backend puts every new connection in this channel.
+const INITIAL_CHANNEL: &str = "test";
+/// The display name sent with comments.
+const NAME_KEY: &str = "comment-feed.name";
+/// Wait before the first reconnect, doubled on every failed attempt.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
 
 pub struct App {
     link: ComponentLink<Self>,
     storage: StorageService,
+    history: History,
+    filter: Filter,
+    search: Search,
     state: State,
     ws_meta: Option<Arc<WsMeta>>,
     ws_stream: Option<Arc<WsStream>>,
+    reconnect: Option<TimeoutTask>,
+    list: List,
+    preview: Preview,
+    /// The next preview frame, while the preview is shown.
+    frame: Option<RenderTask>,
 }
 
 #[derive(Serialize, Clone)]
@@ -46,31 +73,108 @@ struct SendMessageBody {
     action: String,
     channel: String,
     message: String,
+    /// Left out to comment anonymously.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+}
+
+/// A comment and how the overlay shows it, as the overlay reads it.
+#[derive(Serialize)]
+struct Outgoing<'a> {
+    body: &'a str,
+    command: String,
+}
+
+/// A comment as broadcast by the backend, plain text from older senders.
+#[derive(Deserialize)]
+struct Received {
+    body: String,
+    #[serde(default)]
+    command: String,
+    author: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct State {
     channel: String,
     channel_input: String,
-    connected: bool,
+    connection: Connection,
     comments: Vec<Comment>,
     comment_input: String,
+    /// Applied to the comments being sent.
+    #[serde(skip)]
+    command: Command,
+    /// Empty to comment anonymously.
+    name: String,
+    /// Comments typed while disconnected, sent once connected again.
+    outbox: Vec<String>,
+    view: View,
+    /// Comments the filter kept out since joining the channel.
+    hidden: usize,
+    filter_open: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum View {
+    List,
+    /// Comments scrolling as on the overlay.
+    Preview,
+    /// Nothing but the scrolling comments, for a browser source.
+    Overlay,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+enum Connection {
+    /// `attempt` counts the failed attempts before this one.
+    Connecting {
+        attempt: u32,
+    },
+    Open,
+    /// Waiting to connect again.
+    Retrying {
+        attempt: u32,
+    },
+    /// Closed on purpose, no more reconnecting.
+    Closed,
 }
 
 #[derive(Serialize, Deserialize)]
-struct Comment {
+pub struct Comment {
     body: String,
+    /// Command words, empty for plain comments.
+    #[serde(default)]
+    command: String,
+    /// The sender's name, or an anonymous ID given by the backend.
+    #[serde(default)]
+    author: Option<String>,
     time: DateTime<Local>,
 }
 
 pub enum Message {
     UpdateCommentField(String),
+    SetColor(Color),
+    SetPosition(Position),
+    SetSize(Size),
     PushComment,
     UpdateChannelField(String),
+    UpdateName(String),
     Connected(WsMeta, WsStream),
+    ConnectFailed,
     Disconnected,
+    Reconnect,
     SetChannel,
+    Navigated,
     CommentReceived(Comment),
+    ClearHistory,
+    ToggleFilter,
+    UpdateFilter(filter::Field, String),
+    Mute(String),
+    UpdateSearch(search::Field, String),
+    SetRegexSearch(bool),
+    SetView(View),
+    Scrolled,
+    JumpToNewest,
+    Frame,
     Nope,
 }
 
@@ -80,46 +184,60 @@ impl Component for App {
 
     fn create(_: Self::Properties, link: ComponentLink<Self>) -> Self {
         let storage = StorageService::new(Area::Local).unwrap();
-        let comments = {
-            if let Json(Ok(restored_comments)) = storage.restore(KEY) {
-                restored_comments
-            } else {
-                Vec::<Comment>::new()
+        let name = match storage.restore(NAME_KEY) {
+            Json(Ok(name)) => name,
+            _ => String::new(),
+        };
+        let history = History::new();
+        let filter = Filter::load(&storage);
+        let (channel, view, preview) = match route::page() {
+            Page::Main => {
+                let navigated = link.callback(|_| Message::Navigated);
+                route::on_change(move || navigated.emit(()));
+                (route::channel(), View::List, Preview::new())
             }
+            Page::Overlay(overlay) => (
+                overlay.channel,
+                View::Overlay,
+                Preview::fitting(overlay.settings),
+            ),
         };
+        let channel = channel.unwrap_or_else(|| INITIAL_CHANNEL.to_string());
 
         let state = State {
-            channel: "".into(),
-            channel_input: "".into(),
-            connected: false,
-            comments,
+            channel_input: channel.clone(),
+            channel,
+            connection: Connection::Connecting { attempt: 0 },
+            comments: Vec::new(),
             comment_input: "".into(),
+            command: Command::default(),
+            name,
+            outbox: Vec::new(),
+            view,
+            hidden: 0,
+            filter_open: false,
         };
 
-        info!("try connect!");
-        let cloned_link = link.clone();
-        spawn_local(async move {
-            let (ws_meta, ws_stream) = WsMeta::connect(
-                "wss://7ht6ij8i09.execute-api.ap-northeast-1.amazonaws.com/production",
-                None,
-            )
-            .await
-            .expect_throw("failed to connect");
-
-            if ws_meta.ready_state() == WsState::Open {
-                cloned_link.send_message(Message::Connected(ws_meta, ws_stream));
-            } else {
-                unreachable!();
-            }
-        });
-
-        App {
+        let mut app = App {
             link,
             storage,
+            history,
+            filter,
+            search: Search::default(),
             state,
             ws_meta: None,
             ws_stream: None,
+            reconnect: None,
+            list: List::new(),
+            preview,
+            frame: None,
+        };
+        app.restore();
+        if app.state.view == View::Overlay {
+            app.frame = Some(app.request_frame());
         }
+        app.connect(0);
+        app
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
@@ -129,28 +247,38 @@ impl Component for App {
             }
             Message::PushComment => {
                 if !self.state.comment_input.is_empty() {
-                    info!("pushing comment");
-                    self.ws_stream
-                        .as_ref()
-                        .expect("connection naiyo!")
-                        .wrapped()
-                        .send_with_str(
-                            &serde_json::to_string(&SendMessageBody {
-                                action: "sendmessage".to_string(),
-                                channel: self.state.channel.clone(),
-                                message: self.state.comment_input.clone(),
-                            })
-                            .unwrap(),
-                        )
-                        .expect("failed to send");
-
-                    self.state.comment_input = "".to_string();
+                    let body = std::mem::take(&mut self.state.comment_input);
+                    let message = serde_json::to_string(&Outgoing {
+                        body: &body,
+                        command: self.state.command.to_string(),
+                    })
+                    .unwrap();
+                    if !self.send_comment(&message) {
+                        info!("not connected, keeping the comment for later");
+                        self.state.outbox.push(message);
+                    }
                     return true;
                 }
             }
+            Message::SetColor(color) => {
+                self.state.command.color = color;
+                return true;
+            }
+            Message::SetPosition(position) => {
+                self.state.command.position = position;
+                return true;
+            }
+            Message::SetSize(size) => {
+                self.state.command.size = size;
+                return true;
+            }
             Message::UpdateChannelField(body) => {
                 self.state.channel_input = body;
             }
+            Message::UpdateName(name) => {
+                self.storage.store(NAME_KEY, Json(&name));
+                self.state.name = name;
+            }
             Message::Connected(ws_meta, ws_stream) => {
                 self.ws_meta = Some(Arc::new(ws_meta));
                 self.ws_stream = Some(Arc::new(ws_stream));
@@ -162,9 +290,20 @@ impl Component for App {
                     if let Ok(message) = e.data().dyn_into::<JsString>() {
                         info!("message event, received Text: {:?}", message);
 
-                        let comment = Comment {
-                            body: message.into(),
-                            time: Local::now(),
+                        let message = String::from(message);
+                        let comment = match serde_json::from_str::<Received>(&message) {
+                            Ok(received) => Comment {
+                                body: received.body,
+                                command: received.command,
+                                author: received.author,
+                                time: Local::now(),
+                            },
+                            Err(_) => Comment {
+                                body: message,
+                                command: String::new(),
+                                author: None,
+                                time: Local::now(),
+                            },
                         };
                         link.send_message(Message::CommentReceived(comment))
                     }
@@ -179,40 +318,144 @@ impl Component for App {
 
                 callback.forget();
 
-                self.state.connected = true;
+                let link = self.link.clone();
+                let on_close = Closure::wrap(Box::new(move |_: JsValue| {
+                    link.send_message(Message::Disconnected)
+                }) as Box<dyn FnMut(JsValue)>);
+
+                self.ws_stream
+                    .as_ref()
+                    .expect("connection naiyo!")
+                    .wrapped()
+                    .add_event_listener_with_callback("close", on_close.as_ref().unchecked_ref())
+                    .unwrap();
+
+                on_close.forget();
+
+                self.state.connection = Connection::Open;
                 info!("connected!");
+
+                // a new connection starts out in the initial channel
+                if self.state.channel != INITIAL_CHANNEL {
+                    self.send(&SetChannelBody {
+                        action: "setchannel".to_string(),
+                        channel: INITIAL_CHANNEL.to_string(),
+                        new_channel: self.state.channel.clone(),
+                    });
+                }
+                for message in std::mem::take(&mut self.state.outbox) {
+                    if !self.send_comment(&message) {
+                        self.state.outbox.push(message);
+                    }
+                }
                 return true;
             }
+            Message::ConnectFailed => {
+                if let Connection::Connecting { attempt } = self.state.connection {
+                    self.retry(attempt + 1);
+                    return true;
+                }
+            }
             Message::Disconnected => {
                 self.ws_meta = None;
                 self.ws_stream = None;
-                self.state.connected = false;
                 info!("disconnected!");
+                if self.state.connection != Connection::Closed {
+                    self.retry(0);
+                }
                 return true;
             }
+            Message::Reconnect => {
+                if let Connection::Retrying { attempt } = self.state.connection {
+                    self.reconnect = None;
+                    self.connect(attempt);
+                    return true;
+                }
+            }
             Message::CommentReceived(comment) => {
+                if self.filter.hides(&comment.body, comment.author.as_deref()) {
+                    self.state.hidden += 1;
+                    return true;
+                }
+                self.preview
+                    .push(&comment.body, &comment.command, preview::now());
                 self.state.comments.push(comment);
+                self.list.received();
+                self.history.save(&self.state.channel, &self.state.comments);
                 return true;
             }
-            Message::SetChannel => {
-                info!("pushing channel");
-                self.ws_stream
-                    .as_ref()
-                    .expect("connection naiyo!")
-                    .wrapped()
-                    .send_with_str(
-                        &serde_json::to_string(&SetChannelBody {
-                            action: "setchannel".to_string(),
-                            channel: self.state.channel.clone(),
-                            new_channel: self.state.channel_input.clone(),
-                        })
-                        .unwrap(),
-                    )
-                    .expect("failed to send");
-
-                self.state.channel = self.state.channel_input.clone();
+            Message::ToggleFilter => {
+                self.state.filter_open = !self.state.filter_open;
+                return true;
+            }
+            Message::UpdateFilter(field, text) => {
+                self.filter.set(&mut self.storage, field, &text);
+                return true;
+            }
+            Message::Mute(author) => {
+                self.filter.mute(&mut self.storage, &author);
+                let before = self.state.comments.len();
+                self.state
+                    .comments
+                    .retain(|comment| comment.author.as_deref() != Some(author.as_str()));
+                self.state.hidden += before - self.state.comments.len();
+                self.history.save(&self.state.channel, &self.state.comments);
+                return true;
+            }
+            Message::UpdateSearch(field, value) => {
+                self.search.set(field, value);
+                self.list.jump();
+                return true;
+            }
+            Message::SetRegexSearch(regex) => {
+                self.search.set_regex(regex);
+                self.list.jump();
+                return true;
+            }
+            Message::ClearHistory => {
+                self.state.comments.clear();
+                self.history.clear(&self.state.channel);
                 return true;
             }
+            Message::SetView(view) => {
+                self.state.view = view;
+                self.list.jump();
+                self.preview.clear();
+                self.frame = match view {
+                    View::List => None,
+                    View::Preview | View::Overlay => Some(self.request_frame()),
+                };
+                return true;
+            }
+            Message::Scrolled => {
+                self.list.scrolled();
+                return true;
+            }
+            Message::JumpToNewest => {
+                self.list.jump();
+                return true;
+            }
+            Message::Frame => {
+                self.preview.draw(preview::now());
+                if self.state.view != View::List {
+                    self.frame = Some(self.request_frame());
+                }
+            }
+            Message::SetChannel => {
+                if !self.state.channel_input.is_empty() {
+                    self.join(self.state.channel_input.clone());
+                    route::set_channel(&self.state.channel);
+                    return true;
+                }
+            }
+            Message::Navigated => {
+                let channel = route::channel().unwrap_or_else(|| INITIAL_CHANNEL.to_string());
+                if channel != self.state.channel {
+                    self.state.channel_input = channel.clone();
+                    self.join(channel);
+                    return true;
+                }
+            }
             Message::Nope => (),
         }
         false
@@ -222,23 +465,34 @@ impl Component for App {
         false
     }
 
+    fn rendered(&mut self, _first_render: bool) {
+        if self.state.view == View::List {
+            self.list.stick();
+        }
+    }
+
     fn destroy(&mut self) {
         info!("try disconnect!");
-        let link = self.link.clone();
-        let ws_meta = self.ws_meta.as_ref().expect("not connected");
-        let ws_meta = Arc::clone(ws_meta);
-        spawn_local(async move {
-            ws_meta.close().await.expect_throw("failed to close");
-            if ws_meta.ready_state() == WsState::Closed {
-                link.send_message(Message::Disconnected);
-            } else {
-                unreachable!();
-            }
-        });
+        self.state.connection = Connection::Closed;
+        self.reconnect = None;
+        if let Some(ws_meta) = self.ws_meta.as_ref() {
+            let ws_meta = Arc::clone(ws_meta);
+            spawn_local(async move {
+                ws_meta.close().await.expect_throw("failed to close");
+            });
+        }
     }
 
     fn view(&self) -> Html {
         info!("rendered!");
+        if self.state.view == View::Overlay {
+            return html! {
+                <canvas
+                    ref=self.preview.canvas()
+                    style="position: fixed; top: 0; left: 0; width: 100vw; height: 100vh;"
+                />
+            };
+        }
         html! {
             <div>
                 <div class="ui masthead">
@@ -246,6 +500,7 @@ impl Component for App {
                         <h1 class="ui header">
                             { "Comment Feed" }
                         </h1>
+                        { self.view_connection() }
                     </div>
                 </div>
                 <div class="ui divider"/>
@@ -256,16 +511,32 @@ impl Component for App {
                         </div>
 
                         <div class="ui container">
-                            <ul class="ui comments">
-                                {
-                                    for self.state.comments.iter().map( |comment| self.view_comment(comment) )
+                            { self.view_toggle() }
+                            { self.view_filter() }
+                            { self.view_search() }
+                            {
+                                match self.state.view {
+                                    View::List => self.view_comments(),
+                                    View::Preview | View::Overlay => html! {
+                                        <canvas
+                                            ref=self.preview.canvas()
+                                            width=preview::SCREEN.0
+                                            height=preview::SCREEN.1
+                                            style="width: 100%; background: #333;"
+                                        />
+                                    },
                                 }
-                            </ul>
+                            }
                         </div>
 
                         <div class="ui vertical segment">
+                            { self.view_composer() }
                             { self.view_comment_input() }
                         </div>
+
+                        <button class="ui mini basic button" onclick=self.link.callback(|_| Message::ClearHistory)>
+                            { "履歴を消去" }
+                        </button>
                     </div>
                 </div>
             </div>
@@ -274,17 +545,445 @@ impl Component for App {
 }
 
 impl App {
+    fn connect(&mut self, attempt: u32) {
+        info!("try connect!");
+        self.state.connection = Connection::Connecting { attempt };
+        let link = self.link.clone();
+        spawn_local(async move {
+            match WsMeta::connect(ENDPOINT, None).await {
+                Ok((ws_meta, ws_stream)) => {
+                    link.send_message(Message::Connected(ws_meta, ws_stream))
+                }
+                Err(error) => {
+                    warn!("failed to connect: {}", error);
+                    link.send_message(Message::ConnectFailed)
+                }
+            }
+        });
+    }
+
+    fn join(&mut self, channel: String) {
+        info!("pushing channel");
+        // while disconnected, the channel is joined on connecting
+        self.send(&SetChannelBody {
+            action: "setchannel".to_string(),
+            channel: self.state.channel.clone(),
+            new_channel: channel.clone(),
+        });
+
+        self.state.channel = channel;
+        self.restore();
+        self.list.jump();
+    }
+
+    /// Loads the history of the current channel, without what the filter hides now.
+    fn restore(&mut self) {
+        let mut comments = self.history.load(&self.state.channel);
+        let before = comments.len();
+        comments.retain(|comment| !self.filter.hides(&comment.body, comment.author.as_deref()));
+        self.state.hidden = before - comments.len();
+        self.state.comments = comments;
+    }
+
+    /// The comments the search matches, all of them when not searching.
+    fn found(&self) -> Vec<&Comment> {
+        self.state
+            .comments
+            .iter()
+            .filter(|comment| {
+                self.search.matches(
+                    &comment.body,
+                    comment.author.as_deref(),
+                    comment.time.naive_local(),
+                )
+            })
+            .collect()
+    }
+
+    fn request_frame(&self) -> RenderTask {
+        RenderService::request_animation_frame(self.link.callback(|_| Message::Frame))
+    }
+
+    /// Connects again after a delay that grows with every failed attempt.
+    fn retry(&mut self, attempt: u32) {
+        let delay = (RETRY_DELAY * 2u32.saturating_pow(attempt)).min(MAX_RETRY_DELAY);
+        info!("reconnecting in {:?}", delay);
+        self.state.connection = Connection::Retrying { attempt };
+        self.reconnect = Some(TimeoutService::spawn(
+            delay,
+            self.link.callback(|_| Message::Reconnect),
+        ));
+    }
+
+    /// Sends `body` if connected, returns whether it was sent.
+    fn send<T: serde::Serialize>(&self, body: &T) -> bool {
+        match (&self.ws_stream, self.state.connection) {
+            (Some(ws_stream), Connection::Open) => ws_stream
+                .wrapped()
+                .send_with_str(&serde_json::to_string(body).unwrap())
+                .map_err(|error| warn!("failed to send: {:?}", error))
+                .is_ok(),
+            _ => false,
+        }
+    }
+
+    fn send_comment(&self, message: &str) -> bool {
+        self.send(&SendMessageBody {
+            action: "sendmessage".to_string(),
+            channel: self.state.channel.clone(),
+            message: message.to_string(),
+            name: Some(self.state.name.trim())
+                .filter(|name| !name.is_empty())
+                .map(String::from),
+        })
+    }
+
+    fn view_connection(&self) -> Html {
+        let (color, status) = match self.state.connection {
+            Connection::Connecting { .. } => ("yellow", "接続中".to_string()),
+            Connection::Open => ("green", "接続済み".to_string()),
+            Connection::Retrying { attempt } => {
+                ("orange", format!("再接続待ち ({}回目)", attempt + 1))
+            }
+            Connection::Closed => ("grey", "切断".to_string()),
+        };
+        html! {
+            <div>
+                <div class=format!("ui {} label", color)>
+                    { status }
+                    {
+                        if self.state.outbox.is_empty() {
+                            html! {}
+                        } else {
+                            html! {
+                                <div class="detail">
+                                    { format!("未送信 {}", self.state.outbox.len()) }
+                                </div>
+                            }
+                        }
+                    }
+                </div>
+                {
+                    if let Connection::Retrying { .. } = self.state.connection {
+                        html! {
+                            <button class="ui mini basic button" onclick=self.link.callback(|_| Message::Reconnect)>
+                                { "今すぐ再接続" }
+                            </button>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+            </div>
+        }
+    }
+
+    /// The rows in view, between empty space standing in for the others.
+    fn view_comments(&self) -> Html {
+        let comments = self.found();
+        let rows = self.list.rows(comments.len());
+        let space = |rows: usize| format!("height: {}px;", rows as f64 * list::ROW_HEIGHT);
+        html! {
+            <div style="position: relative;">
+                <div
+                    ref=self.list.node()
+                    style="height: 60vh; overflow-y: auto;"
+                    onscroll=self.link.callback(|_| Message::Scrolled)
+                >
+                    <div class="ui comments" style="max-width: none;">
+                        <div style=space(rows.start)/>
+                        { for comments[rows.clone()].iter().map(|&comment| self.view_comment(comment)) }
+                        <div style=space(comments.len() - rows.end)/>
+                    </div>
+                </div>
+                {
+                    if self.list.unseen() > 0 {
+                        html! {
+                            <button
+                                class="ui mini primary button"
+                                style="position: absolute; bottom: 1em; left: 50%; transform: translateX(-50%);"
+                                onclick=self.link.callback(|_| Message::JumpToNewest)
+                            >
+                                { format!("新着コメント {}件", self.list.unseen()) }
+                            </button>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+            </div>
+        }
+    }
+
     fn view_comment(&self, comment: &Comment) -> Html {
         html! {
-            <div class="comment">
+            <div
+                class="comment"
+                style=format!("height: {}px; margin: 0; overflow: hidden; white-space: nowrap; text-overflow: ellipsis;", list::ROW_HEIGHT)
+            >
                 <div class="content">
+                    <span class="author">
+                        { comment.author.as_deref().unwrap_or("") }
+                    </span>
+                    {
+                        match &comment.author {
+                            Some(author) => {
+                                let author = author.clone();
+                                html! {
+                                    <a class="mute" title="この人のコメントを非表示" onclick=self.link.callback(move |_| Message::Mute(author.clone()))>
+                                        { "ミュート" }
+                                    </a>
+                                }
+                            }
+                            None => html! {},
+                        }
+                    }
                     <div class="metadata">
-                        { &comment.time }
+                        <span class="date">{ &comment.time }</span>
+                        <span class="command">{ &comment.command }</span>
                     </div>
                     <div class="text">
-                        { &comment.body }
+                        {
+                            for self.search.highlight(&comment.body).into_iter().map(|(text, found)| {
+                                if found {
+                                    html! { <mark>{ text }</mark> }
+                                } else {
+                                    html! { { text } }
+                                }
+                            })
+                        }
+                    </div>
+                </div>
+            </div>
+        }
+    }
+
+    /// Hidden comment count, and the filter settings when opened.
+    fn view_filter(&self) -> Html {
+        let rules = self.filter.rules();
+        let list = |field: filter::Field, label: &str, entries: &[String], placeholder: &str| {
+            html! {
+                <div class="field">
+                    <label>{ label }</label>
+                    <textarea
+                        rows="3"
+                        placeholder=placeholder
+                        value=entries.join("\n")
+                        onchange=self.link.callback(move |change: ChangeData| match change {
+                            ChangeData::Value(text) => Message::UpdateFilter(field, text),
+                            _ => Message::Nope,
+                        })
+                    />
+                </div>
+            }
+        };
+        let settings = if self.state.filter_open {
+            html! {
+                <div class="ui form segment">
+                    <div class="three fields">
+                        { list(filter::Field::Words, "NGワード", &rules.words, "1行に1つ") }
+                        { list(filter::Field::Patterns, "正規表現", &rules.patterns, "1行に1つ") }
+                        { list(filter::Field::Muted, "ミュート", &rules.muted, "名前かID、1行に1つ") }
+                    </div>
+                    <div class="inline field">
+                        <label>{ "最短の文字数" }</label>
+                        <input
+                            type="number"
+                            min="0"
+                            value=rules.min_length.to_string()
+                            onchange=self.link.callback(|change: ChangeData| match change {
+                                ChangeData::Value(text) => Message::UpdateFilter(filter::Field::MinLength, text),
+                                _ => Message::Nope,
+                            })
+                        />
+                    </div>
+                    {
+                        if self.filter.invalid().is_empty() {
+                            html! {}
+                        } else {
+                            html! {
+                                <div class="ui visible warning message">
+                                    { format!("正しくない正規表現を無視しています: {}", self.filter.invalid().join(", ")) }
+                                </div>
+                            }
+                        }
+                    }
+                </div>
+            }
+        } else {
+            html! {}
+        };
+        html! {
+            <>
+                <button class="ui mini basic button" onclick=self.link.callback(|_| Message::ToggleFilter)>
+                    { "フィルター" }
+                    <div class="ui mini label">{ format!("非表示 {}", self.state.hidden) }</div>
+                </button>
+                { settings }
+            </>
+        }
+    }
+
+    fn view_search(&self) -> Html {
+        let input = |field: search::Field, kind: &str, placeholder: &str| {
+            html! {
+                <div class="field">
+                    <input
+                        type=kind
+                        placeholder=placeholder
+                        value=self.search.get(field)
+                        oninput=self.link.callback(move |e: InputData| Message::UpdateSearch(field, e.value))
+                    />
+                </div>
+            }
+        };
+        let regex = self.search.is_regex();
+        let count = if self.search.is_empty() {
+            html! {}
+        } else {
+            let found = self.found().len();
+            html! {
+                <div class="ui label">{ format!("{} / {}件", found, self.state.comments.len()) }</div>
+            }
+        };
+        html! {
+            <div class="ui form">
+                <div class="fields">
+                    { input(search::Field::Text, "search", "検索") }
+                    <div class="field">
+                        <div class="ui checkbox">
+                            <input
+                                type="checkbox"
+                                checked=regex
+                                onclick=self.link.callback(move |_| Message::SetRegexSearch(!regex))
+                            />
+                            <label>{ "正規表現" }</label>
+                        </div>
+                    </div>
+                    { input(search::Field::Author, "search", "名前かID") }
+                    { input(search::Field::From, "datetime-local", "") }
+                    { input(search::Field::To, "datetime-local", "") }
+                    <div class="field">{ count }</div>
+                </div>
+                {
+                    match self.search.error() {
+                        Some(error) => html! {
+                            <div class="ui visible warning message">{ error }</div>
+                        },
+                        None => html! {},
+                    }
+                }
+            </div>
+        }
+    }
+
+    fn view_toggle(&self) -> Html {
+        let button = |view: View, label: &str| {
+            let class = if self.state.view == view {
+                "ui active button"
+            } else {
+                "ui button"
+            };
+            html! {
+                <button class=class onclick=self.link.callback(move |_| Message::SetView(view))>
+                    { label }
+                </button>
+            }
+        };
+        html! {
+            <div class="ui mini buttons">
+                { button(View::List, "リスト") }
+                { button(View::Preview, "プレビュー") }
+            </div>
+        }
+    }
+
+    /// Command pickers and the comment as the overlay would show it.
+    fn view_composer(&self) -> Html {
+        let command = self.state.command;
+        let active = |selected: bool| {
+            if selected {
+                "ui active button"
+            } else {
+                "ui button"
+            }
+        };
+        let sizes = Size::ALL.iter().map(|&size| {
+            html! {
+                <button class=active(size == command.size) onclick=self.link.callback(move |_| Message::SetSize(size))>
+                    { size.name() }
+                </button>
+            }
+        });
+        let positions = Position::ALL.iter().map(|&position| {
+            html! {
+                <button class=active(position == command.position) onclick=self.link.callback(move |_| Message::SetPosition(position))>
+                    { position.name() }
+                </button>
+            }
+        });
+        let colors = Color::named().map(|(name, color)| {
+            let class = if color == command.color {
+                "ui circular empty label active"
+            } else {
+                "ui circular empty label"
+            };
+            html! {
+                <a
+                    class=class
+                    title=name
+                    style=format!("background-color: {}; border: 1px solid #888;", css_color(color))
+                    onclick=self.link.callback(move |_| Message::SetColor(color))
+                />
+            }
+        });
+
+        let (justify, align) = match command.position {
+            Position::Scroll => ("flex-start", "center"),
+            Position::Top => ("center", "flex-start"),
+            Position::Bottom => ("center", "flex-end"),
+        };
+        let preview_style = format!(
+            "display: flex; justify-content: {}; align-items: {}; height: 80px; padding: 8px; \
+             overflow: hidden; white-space: nowrap; background: #333; color: {}; font-size: {}em; \
+             font-weight: bold; text-shadow: -1px -1px 0 #000, 1px -1px 0 #000, -1px 1px 0 #000, 1px 1px 0 #000;",
+            justify,
+            align,
+            css_color(command.color),
+            command.size.scale(),
+        );
+        let preview = if self.state.comment_input.is_empty() {
+            "プレビュー"
+        } else {
+            &self.state.comment_input
+        };
+
+        html! {
+            <div class="ui form">
+                <div class="inline fields">
+                    <div class="field">
+                        <div class="ui mini buttons">{ for sizes }</div>
+                    </div>
+                    <div class="field">
+                        <div class="ui mini buttons">{ for positions }</div>
+                    </div>
+                    <div class="field">
+                        { for colors }
+                        <input
+                            type="color"
+                            value=css_color(command.color)
+                            oninput=self.link.callback(|e: InputData| {
+                                match Color::from_name(&e.value) {
+                                    Some(color) => Message::SetColor(color),
+                                    None => Message::Nope,
+                                }
+                            })
+                        />
                     </div>
                 </div>
+                <div class="preview" style=preview_style>
+                    { preview }
+                </div>
             </div>
         }
     }
@@ -292,6 +991,13 @@ impl App {
     fn view_comment_input(&self) -> Html {
         html! {
             <div class="ui fluid action input">
+                <input
+                    type="text"
+                    class="name"
+                    placeholder="名前 (空欄で匿名)"
+                    value=&self.state.name
+                    oninput=self.link.callback(move |e: InputData| Message::UpdateName(e.value))
+                />
                 <input
                     type="text"
                     value=&self.state.comment_input
@@ -325,3 +1031,9 @@ impl App {
         }
     }
 }
+
+/// `#rrggbb`, which CSS and color inputs understand unlike some of the command color names.
+fn css_color(color: Color) -> String {
+    let Color(r, g, b) = color;
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
@@ -0,0 +1,171 @@
+//! Hiding comments by what they say or who sent them, set up by each viewer for themselves.
+
+use log::warn;
+use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
+use yew::format::Json;
+use yew::services::storage::StorageService;
+
+const KEY: &str = "comment-feed.filter";
+
+/// What the viewer typed in, kept in local storage.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Rules {
+    /// Hides comments containing any of these, ignoring case.
+    pub words: Vec<String>,
+    /// Hides comments matching any of these regular expressions.
+    pub patterns: Vec<String>,
+    /// Names and anonymous IDs.
+    pub muted: Vec<String>,
+    /// Hides comments shorter than this many characters.
+    pub min_length: usize,
+}
+
+#[derive(Clone, Copy)]
+pub enum Field {
+    Words,
+    Patterns,
+    Muted,
+    MinLength,
+}
+
+pub struct Filter {
+    rules: Rules,
+    compiled: Vec<Regex>,
+    /// Patterns that aren't valid regular expressions, ignored.
+    invalid: Vec<String>,
+}
+
+impl Filter {
+    pub fn load(storage: &StorageService) -> Self {
+        match storage.restore(KEY) {
+            Json(Ok(rules)) => Filter::new(rules),
+            _ => Filter::new(Rules::default()),
+        }
+    }
+
+    fn new(rules: Rules) -> Self {
+        let mut filter = Filter {
+            rules,
+            compiled: Vec::new(),
+            invalid: Vec::new(),
+        };
+        filter.compile();
+        filter
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    pub fn invalid(&self) -> &[String] {
+        &self.invalid
+    }
+
+    /// Sets `field` from the text in its input, one entry per line for lists.
+    pub fn set(&mut self, storage: &mut StorageService, field: Field, text: &str) {
+        let lines = || {
+            text.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(String::from)
+                .collect()
+        };
+        match field {
+            Field::Words => self.rules.words = lines(),
+            Field::Patterns => {
+                self.rules.patterns = lines();
+                self.compile();
+            }
+            Field::Muted => self.rules.muted = lines(),
+            Field::MinLength => self.rules.min_length = text.trim().parse().unwrap_or(0),
+        }
+        storage.store(KEY, Json(&self.rules));
+    }
+
+    pub fn mute(&mut self, storage: &mut StorageService, author: &str) {
+        if !self.rules.muted.iter().any(|muted| muted == author) {
+            self.rules.muted.push(author.to_string());
+            storage.store(KEY, Json(&self.rules));
+        }
+    }
+
+    pub fn hides(&self, body: &str, author: Option<&str>) -> bool {
+        let Rules {
+            words,
+            muted,
+            min_length,
+            ..
+        } = &self.rules;
+        let lowercase = body.to_lowercase();
+
+        author.is_some_and(|author| muted.iter().any(|muted| muted == author))
+            || body.chars().count() < *min_length
+            || words
+                .iter()
+                .any(|word| lowercase.contains(&word.to_lowercase()))
+            || self.compiled.iter().any(|pattern| pattern.is_match(body))
+    }
+
+    fn compile(&mut self) {
+        self.compiled.clear();
+        self.invalid.clear();
+        for pattern in &self.rules.patterns {
+            match Regex::new(pattern) {
+                Ok(regex) => self.compiled.push(regex),
+                Err(error) => {
+                    warn!("ignoring {:?}: {}", pattern, error);
+                    self.invalid.push(pattern.clone());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_ignore_case() {
+        let filter = Filter::new(Rules {
+            words: vec!["Spoiler".into()],
+            ..Rules::default()
+        });
+        assert!(filter.hides("no SPOILERS please", None));
+        assert!(!filter.hides("spoil", None));
+    }
+
+    #[test]
+    fn min_length_counts_characters() {
+        let filter = Filter::new(Rules {
+            min_length: 3,
+            ..Rules::default()
+        });
+        // six bytes, but two characters
+        assert!(filter.hides("草草", None));
+        assert!(!filter.hides("草草草", None));
+    }
+
+    #[test]
+    fn muted_authors_are_matched_exactly() {
+        let filter = Filter::new(Rules {
+            muted: vec!["ID:1a2b3c4d".into()],
+            ..Rules::default()
+        });
+        assert!(filter.hides("hi", Some("ID:1a2b3c4d")));
+        assert!(!filter.hides("hi", Some("ID:1a2b3c4")));
+        assert!(!filter.hides("hi", None));
+    }
+
+    #[test]
+    fn invalid_patterns_are_ignored() {
+        let filter = Filter::new(Rules {
+            patterns: vec!["^w+$".into(), "(".into()],
+            ..Rules::default()
+        });
+        assert!(filter.hides("wwww", None));
+        assert!(!filter.hides("www.example.com", None));
+        assert_eq!(filter.invalid(), ["(".to_string()]);
+    }
+}
@@ -0,0 +1,68 @@
+//! Received comments kept in local storage, one list per channel.
+
+use yew::format::Json;
+use yew::services::storage::{Area, StorageService};
+
+use crate::app::Comment;
+
+/// Channels with a history, most recently used first.
+const INDEX_KEY: &str = "comment-feed.history";
+/// Left over from the template the app started as.
+const LEGACY_KEY: &str = "yew.todomvc.self";
+/// Comments kept per channel, older ones are dropped.
+const MAX_COMMENTS: usize = 500;
+/// Channels kept, the least recently used one is dropped.
+const MAX_CHANNELS: usize = 20;
+
+pub struct History {
+    storage: StorageService,
+}
+
+impl History {
+    pub fn new() -> Self {
+        let mut storage = StorageService::new(Area::Local).unwrap();
+        storage.remove(LEGACY_KEY);
+        History { storage }
+    }
+
+    pub fn load(&self, channel: &str) -> Vec<Comment> {
+        match self.storage.restore(&key(channel)) {
+            Json(Ok(comments)) => comments,
+            _ => Vec::new(),
+        }
+    }
+
+    /// Stores the latest comments of `channel`.
+    pub fn save(&mut self, channel: &str, comments: &[Comment]) {
+        let kept = &comments[comments.len().saturating_sub(MAX_COMMENTS)..];
+        self.storage.store(&key(channel), Json(&kept));
+
+        let mut channels = self.channels();
+        if channels.first().map(String::as_str) != Some(channel) {
+            channels.retain(|used| used != channel);
+            channels.insert(0, channel.to_string());
+            for evicted in channels.drain(MAX_CHANNELS.min(channels.len())..) {
+                self.storage.remove(&key(&evicted));
+            }
+            self.storage.store(INDEX_KEY, Json(&channels));
+        }
+    }
+
+    pub fn clear(&mut self, channel: &str) {
+        self.storage.remove(&key(channel));
+        let mut channels = self.channels();
+        channels.retain(|used| used != channel);
+        self.storage.store(INDEX_KEY, Json(&channels));
+    }
+
+    fn channels(&self) -> Vec<String> {
+        match self.storage.restore(INDEX_KEY) {
+            Json(Ok(channels)) => channels,
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn key(channel: &str) -> String {
+    format!("{}.{}", INDEX_KEY, channel)
+}
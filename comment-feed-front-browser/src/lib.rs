@@ -1,6 +1,12 @@
 #![recursion_limit = "512"]
 
 mod app;
+mod filter;
+mod history;
+mod list;
+mod preview;
+mod route;
+mod search;
 
 use wasm_bindgen::prelude::*;
 
@@ -0,0 +1,96 @@
+//! The comment list only puts the rows in view in the page, so that long sessions stay fast.
+//! Rows have a fixed height, the rest of the list is empty space around them.
+
+use std::ops::Range;
+
+use web_sys::Element;
+use yew::NodeRef;
+
+/// Pixels, longer comments are cut.
+pub const ROW_HEIGHT: f64 = 72.0;
+/// Rows rendered past each end of the view, so that scrolling doesn't show gaps.
+const OVERSCAN: usize = 5;
+
+pub struct List {
+    node: NodeRef,
+    scroll_top: f64,
+    height: f64,
+    /// Keep the newest comment in view.
+    follow: bool,
+    /// Comments received while not following.
+    unseen: usize,
+}
+
+impl List {
+    pub fn new() -> Self {
+        List {
+            node: NodeRef::default(),
+            scroll_top: 0.0,
+            height: 0.0,
+            follow: true,
+            unseen: 0,
+        }
+    }
+
+    /// To be put on the scrolling element.
+    pub fn node(&self) -> NodeRef {
+        self.node.clone()
+    }
+
+    pub fn unseen(&self) -> usize {
+        self.unseen
+    }
+
+    /// Rows to render out of `len`.
+    pub fn rows(&self, len: usize) -> Range<usize> {
+        // the view hasn't been measured yet, start from the end
+        let height = if self.height > 0.0 {
+            self.height
+        } else {
+            ROW_HEIGHT * 10.0
+        };
+        let scroll_top = if self.follow {
+            len as f64 * ROW_HEIGHT - height
+        } else {
+            self.scroll_top
+        };
+        let first = (scroll_top / ROW_HEIGHT).floor().max(0.0) as usize;
+        let last = ((scroll_top + height) / ROW_HEIGHT).ceil().max(0.0) as usize;
+        first.saturating_sub(OVERSCAN).min(len)..(last + OVERSCAN).min(len)
+    }
+
+    /// Reads the scroll position after the user scrolled. Scrolling to the end follows again.
+    pub fn scrolled(&mut self) {
+        if let Some(element) = self.node.cast::<Element>() {
+            self.scroll_top = f64::from(element.scroll_top());
+            self.height = f64::from(element.client_height());
+            let bottom = f64::from(element.scroll_height()) - self.height;
+            self.follow = self.scroll_top >= bottom - ROW_HEIGHT / 2.0;
+            if self.follow {
+                self.unseen = 0;
+            }
+        }
+    }
+
+    pub fn received(&mut self) {
+        if !self.follow {
+            self.unseen += 1;
+        }
+    }
+
+    /// Follows the newest comment again.
+    pub fn jump(&mut self) {
+        self.follow = true;
+        self.unseen = 0;
+    }
+
+    /// Scrolls to the end after rendering while following.
+    pub fn stick(&self) {
+        if !self.follow {
+            return;
+        }
+        if let Some(element) = self.node.cast::<Element>() {
+            element.set_scroll_top(element.scroll_height());
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! Comments scrolling over a canvas the way the desktop overlay shows them, using the same
+//! layout. Either a full HD screen with the overlay's default sizes, or the whole page for use as
+//! a browser source.
+
+use comment_layout::{Command, Config, Layout, FIXED_DURATION, FONT_SIZE, SCROLL_SPEED};
+use wasm_bindgen::JsCast;
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
+use yew::NodeRef;
+
+/// The screen being imitated. The canvas is scaled down to fit the page.
+pub const SCREEN: (f32, f32) = (1920.0, 1080.0);
+/// Lane height relative to the font size, about what the overlay's font gives.
+const LINE_HEIGHT: f32 = 1.2;
+const OUTLINE_WIDTH: f64 = 4.0;
+
+pub struct Settings {
+    /// Font size of `medium` comments in pixels.
+    pub font_size: f32,
+    /// Pixels per second.
+    pub speed: f32,
+    /// Splits the height into this many lanes instead of lanes one line high.
+    pub lanes: Option<usize>,
+    pub opacity: f32,
+}
+
+/// The overlay's defaults.
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            font_size: FONT_SIZE,
+            speed: SCROLL_SPEED,
+            lanes: None,
+            opacity: 1.0,
+        }
+    }
+}
+
+impl Settings {
+    fn layout_config(&self, width: f32, height: f32) -> Config {
+        let lane_height = match self.lanes {
+            Some(lanes) if lanes > 0 => height / lanes as f32,
+            _ => self.font_size * LINE_HEIGHT,
+        };
+        Config {
+            width,
+            height,
+            lane_height,
+            speed: self.speed,
+            fixed_duration: FIXED_DURATION,
+        }
+    }
+}
+
+struct Shown {
+    body: String,
+    command: Command,
+}
+
+pub struct Preview {
+    canvas: NodeRef,
+    layout: Layout<Shown>,
+    settings: Settings,
+    /// Follow the size the canvas is shown at rather than imitating [`SCREEN`].
+    fit: bool,
+}
+
+impl Preview {
+    /// Imitates [`SCREEN`] with the overlay's defaults.
+    pub fn new() -> Self {
+        let settings = Settings::default();
+        Preview {
+            canvas: NodeRef::default(),
+            layout: Layout::new(settings.layout_config(SCREEN.0, SCREEN.1)),
+            settings,
+            fit: false,
+        }
+    }
+
+    /// Draws at the size the canvas is shown at.
+    pub fn fitting(settings: Settings) -> Self {
+        Preview {
+            canvas: NodeRef::default(),
+            layout: Layout::new(settings.layout_config(0.0, 0.0)),
+            settings,
+            fit: true,
+        }
+    }
+
+    /// To be put on the `<canvas>`.
+    pub fn canvas(&self) -> NodeRef {
+        self.canvas.clone()
+    }
+
+    /// Sends `body` across the screen. Comments arriving while the canvas isn't shown are
+    /// dropped, like on a hidden overlay.
+    pub fn push(&mut self, body: &str, command: &str, time: f64) {
+        if self.fit {
+            self.fit_canvas();
+        }
+        let context = match self.context() {
+            Some(context) => context,
+            None => return,
+        };
+        let command = Command::parse(command);
+        let font_size = self.settings.font_size * command.size.scale();
+        context.set_font(&font(font_size));
+        let width = context
+            .measure_text(body)
+            .map_or(0.0, |metrics| metrics.width() as f32);
+
+        self.layout.place(
+            Shown {
+                body: body.to_string(),
+                command,
+            },
+            command.position,
+            width,
+            font_size * LINE_HEIGHT,
+            time,
+        );
+    }
+
+    pub fn draw(&mut self, time: f64) {
+        if self.fit {
+            self.fit_canvas();
+        }
+        let context = match self.context() {
+            Some(context) => context,
+            None => return,
+        };
+        self.layout.expire(time);
+
+        let Config { width, height, .. } = *self.layout.config();
+        context.clear_rect(0.0, 0.0, width.into(), height.into());
+        context.set_global_alpha(self.settings.opacity.into());
+        context.set_text_baseline("top");
+        context.set_line_join("round");
+        context.set_line_width(OUTLINE_WIDTH);
+        context.set_stroke_style(&"#000".into());
+
+        for placement in self.layout.positions(time) {
+            let Shown { body, command } = placement.item;
+            let font_size = self.settings.font_size * command.size.scale();
+            let (x, y) = (f64::from(placement.x), f64::from(placement.y));
+            let color = command.color;
+            context.set_font(&font(font_size));
+            context.set_fill_style(&format!("rgb({}, {}, {})", color.0, color.1, color.2).into());
+            context.stroke_text(body, x, y).ok();
+            context.fill_text(body, x, y).ok();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.layout.clear();
+    }
+
+    /// Matches the canvas resolution and the layout to the size the canvas is shown at.
+    fn fit_canvas(&mut self) {
+        let canvas = match self.canvas.cast::<HtmlCanvasElement>() {
+            Some(canvas) => canvas,
+            None => return,
+        };
+        let (width, height) = (canvas.client_width() as u32, canvas.client_height() as u32);
+        if (canvas.width(), canvas.height()) != (width, height) {
+            canvas.set_width(width);
+            canvas.set_height(height);
+            self.layout
+                .set_config(self.settings.layout_config(width as f32, height as f32));
+        }
+    }
+
+    fn context(&self) -> Option<CanvasRenderingContext2d> {
+        self.canvas
+            .cast::<HtmlCanvasElement>()?
+            .get_context("2d")
+            .ok()??
+            .dyn_into()
+            .ok()
+    }
+}
+
+fn font(size: f32) -> String {
+    format!("bold {}px sans-serif", size)
+}
+
+/// Seconds on the clock comments are placed and drawn by.
+pub fn now() -> f64 {
+    js_sys::Date::now() / 1000.0
+}
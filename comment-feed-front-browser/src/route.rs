@@ -0,0 +1,124 @@
+//! The channel in the page URL, as `#channel`, so that a link can join a channel directly, and
+//! the browser source page at `overlay/`.
+//!
+//! The fragment works on any static host, a path would need the server to serve the app for it.
+//! `overlay/` has its own `index.html` for the same reason.
+
+use js_sys::{decode_uri_component, encode_uri_component};
+use log::warn;
+use wasm_bindgen::{prelude::*, JsCast};
+use web_sys::UrlSearchParams;
+
+use crate::preview::Settings;
+
+/// More lanes than this would make comments too small to read on any screen.
+const MAX_LANES: usize = 100;
+
+pub enum Page {
+    Main,
+    /// Only the comments, on a transparent page.
+    Overlay(Overlay),
+}
+
+/// Read from the query, e.g. `overlay/?channel=x&font_size=40&speed=150&lanes=12&opacity=0.8`.
+pub struct Overlay {
+    pub channel: Option<String>,
+    pub settings: Settings,
+}
+
+pub fn page() -> Page {
+    let location = match web_sys::window() {
+        Some(window) => window.location(),
+        None => return Page::Main,
+    };
+    let path = location.pathname().unwrap_or_default();
+    let path = path.trim_end_matches("index.html").trim_end_matches('/');
+    if !path.ends_with("/overlay") {
+        return Page::Main;
+    }
+
+    let query = location.search().unwrap_or_default();
+    let params = UrlSearchParams::new_with_str(&query).ok();
+    let param = |name: &str| params.as_ref()?.get(name).filter(|value| !value.is_empty());
+    let defaults = Settings::default();
+    Page::Overlay(Overlay {
+        channel: param("channel"),
+        settings: Settings {
+            font_size: positive("font_size", param("font_size")).unwrap_or(defaults.font_size),
+            speed: positive("speed", param("speed")).unwrap_or(defaults.speed),
+            lanes: lanes(param("lanes")),
+            opacity: number(param("opacity"))
+                .filter(|opacity: &f32| opacity.is_finite())
+                .map_or(defaults.opacity, |opacity| opacity.clamp(0.0, 1.0)),
+        },
+    })
+}
+
+fn number<T: std::str::FromStr>(value: Option<String>) -> Option<T> {
+    let value = value?;
+    let parsed = value.parse().ok();
+    if parsed.is_none() {
+        warn!("ignoring {:?}, not a number", value);
+    }
+    parsed
+}
+
+fn positive(name: &str, value: Option<String>) -> Option<f32> {
+    let value: f32 = number(value)?;
+    if value.is_finite() && value > 0.0 {
+        Some(value)
+    } else {
+        warn!("ignoring {} {}, it has to be above 0", name, value);
+        None
+    }
+}
+
+fn lanes(value: Option<String>) -> Option<usize> {
+    match number(value)? {
+        0 => {
+            warn!("ignoring 0 lanes");
+            None
+        }
+        lanes if lanes > MAX_LANES => {
+            warn!("{} lanes are too many, using {}", lanes, MAX_LANES);
+            Some(MAX_LANES)
+        }
+        lanes => Some(lanes),
+    }
+}
+
+/// The channel in the URL, if any.
+pub fn channel() -> Option<String> {
+    let hash = web_sys::window()?.location().hash().ok()?;
+    let encoded = hash.trim_start_matches('#');
+    if encoded.is_empty() {
+        return None;
+    }
+    let channel = decode_uri_component(encoded)
+        .map(String::from)
+        .unwrap_or_else(|_| encoded.to_string());
+    Some(channel)
+}
+
+/// Puts `channel` in the URL as a new history entry.
+pub fn set_channel(channel: &str) {
+    if self::channel().as_deref() == Some(channel) {
+        return;
+    }
+    if let Some(window) = web_sys::window() {
+        let encoded = String::from(encode_uri_component(channel));
+        if let Err(error) = window.location().set_hash(&encoded) {
+            warn!("failed to update the URL: {:?}", error);
+        }
+    }
+}
+
+/// Calls `callback` whenever the URL changes, by the back and forward buttons among others.
+pub fn on_change(mut callback: impl FnMut() + 'static) {
+    let listener = Closure::wrap(Box::new(move |_: JsValue| callback()) as Box<dyn FnMut(JsValue)>);
+    web_sys::window()
+        .expect("no window")
+        .add_event_listener_with_callback("hashchange", listener.as_ref().unchecked_ref())
+        .unwrap();
+    listener.forget();
+}
@@ -0,0 +1,189 @@
+//! Narrowing the comment list down by text, author and time, with the matching text marked.
+
+use chrono::NaiveDateTime;
+use regex::{Regex, RegexBuilder};
+
+/// As `<input type="datetime-local">` gives it.
+const DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M";
+
+#[derive(Clone, Copy)]
+pub enum Field {
+    Text,
+    Author,
+    /// Earliest time, inclusive.
+    From,
+    /// Latest time, inclusive to the minute.
+    To,
+}
+
+#[derive(Default)]
+pub struct Search {
+    text: String,
+    /// Take `text` as a regular expression rather than plain text.
+    regex: bool,
+    author: String,
+    from: String,
+    to: String,
+    /// `None` while `text` is empty or invalid.
+    pattern: Option<Regex>,
+    /// Why `text` isn't a valid regular expression.
+    error: Option<String>,
+}
+
+impl Search {
+    pub fn get(&self, field: Field) -> &str {
+        match field {
+            Field::Text => &self.text,
+            Field::Author => &self.author,
+            Field::From => &self.from,
+            Field::To => &self.to,
+        }
+    }
+
+    pub fn set(&mut self, field: Field, value: String) {
+        match field {
+            Field::Text => self.text = value,
+            Field::Author => self.author = value,
+            Field::From => self.from = value,
+            Field::To => self.to = value,
+        }
+        self.compile();
+    }
+
+    pub fn is_regex(&self) -> bool {
+        self.regex
+    }
+
+    pub fn set_regex(&mut self, regex: bool) {
+        self.regex = regex;
+        self.compile();
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        [&self.text, &self.author, &self.from, &self.to]
+            .iter()
+            .all(|field| field.is_empty())
+    }
+
+    pub fn matches(&self, body: &str, author: Option<&str>, time: NaiveDateTime) -> bool {
+        let author_matches = self.author.is_empty()
+            || author
+                .is_some_and(|author| author.to_lowercase().contains(&self.author.to_lowercase()));
+        let after = parse_time(&self.from).is_none_or(|from| time >= from);
+        // the input has no seconds, so the whole last minute counts
+        let before = parse_time(&self.to).is_none_or(|to| time < to + chrono::Duration::minutes(1));
+        let text_matches = self
+            .pattern
+            .as_ref()
+            .is_none_or(|pattern| pattern.is_match(body));
+
+        author_matches && after && before && text_matches
+    }
+
+    /// `body` cut into pieces, each marked if it matches the text searched for.
+    pub fn highlight<'a>(&self, body: &'a str) -> Vec<(&'a str, bool)> {
+        let pattern = match &self.pattern {
+            Some(pattern) => pattern,
+            None => return vec![(body, false)],
+        };
+        let mut pieces = Vec::new();
+        let mut end = 0;
+        for found in pattern
+            .find_iter(body)
+            .filter(|found| !found.as_str().is_empty())
+        {
+            if end < found.start() {
+                pieces.push((&body[end..found.start()], false));
+            }
+            pieces.push((found.as_str(), true));
+            end = found.end();
+        }
+        if end < body.len() {
+            pieces.push((&body[end..], false));
+        }
+        pieces
+    }
+
+    fn compile(&mut self) {
+        self.pattern = None;
+        self.error = None;
+        if self.text.is_empty() {
+            return;
+        }
+        let pattern = if self.regex {
+            RegexBuilder::new(&self.text).build()
+        } else {
+            RegexBuilder::new(&regex::escape(&self.text))
+                .case_insensitive(true)
+                .build()
+        };
+        match pattern {
+            Ok(pattern) => self.pattern = Some(pattern),
+            Err(error) => self.error = Some(error.to_string()),
+        }
+    }
+}
+
+fn parse_time(value: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(value, DATETIME_FORMAT).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S").unwrap()
+    }
+
+    fn search(field: Field, value: &str) -> Search {
+        let mut search = Search::default();
+        search.set(field, value.to_string());
+        search
+    }
+
+    #[test]
+    fn to_includes_its_whole_minute() {
+        let search = search(Field::To, "2021-01-02T03:04");
+        assert!(search.matches("", None, time("2021-01-02T03:04:59")));
+        assert!(!search.matches("", None, time("2021-01-02T03:05:00")));
+    }
+
+    #[test]
+    fn from_is_inclusive() {
+        let search = search(Field::From, "2021-01-02T03:04");
+        assert!(search.matches("", None, time("2021-01-02T03:04:00")));
+        assert!(!search.matches("", None, time("2021-01-02T03:03:59")));
+    }
+
+    #[test]
+    fn plain_text_ignores_case_and_regex_syntax() {
+        let search = search(Field::Text, "A.B");
+        assert!(search.matches("xa.by", None, time("2021-01-02T03:04:00")));
+        assert!(!search.matches("axb", None, time("2021-01-02T03:04:00")));
+    }
+
+    #[test]
+    fn highlight_splits_multibyte_text() {
+        let search = search(Field::Text, "日本");
+        assert_eq!(
+            search.highlight("こんにちは日本語"),
+            vec![("こんにちは", false), ("日本", true), ("語", false)]
+        );
+    }
+
+    #[test]
+    fn highlight_skips_empty_matches() {
+        let mut search = search(Field::Text, "x*");
+        search.set_regex(true);
+        assert_eq!(
+            search.highlight("axxbé"),
+            vec![("a", false), ("xx", true), ("bé", false)]
+        );
+        assert!(search.error().is_none());
+    }
+}
@@ -15,6 +15,8 @@ module.exports = (env, argv) => {
     output: {
       path: distPath,
       filename: "comment-feed.js",
+      // chunks and wasm are fetched from the root, also by overlay/
+      publicPath: '/',
       webassemblyModuleFilename: "comment-feed.wasm"
     },
     module: {
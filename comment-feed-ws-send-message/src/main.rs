@@ -5,7 +5,7 @@ use log::{error, info};
 use rusoto_core::Region;
 use rusoto_dynamodb::{DynamoDb, DynamoDbClient, UpdateItemInput, QueryInput, GetItemInput};
 use serde_derive::{Deserialize, Serialize};
-use serde_json;
+use serde_json::{self, json, Value};
 use simple_logger;
 use rusoto_apigatewaymanagementapi::{ApiGatewayManagementApi, ApiGatewayManagementApiClient, PostToConnectionRequest};
 use futures::stream::{futures_unordered::FuturesUnordered, StreamExt};
@@ -14,6 +14,11 @@ use lambda::error::HandlerError;
 
 use std::{error::Error, collections::HashMap};
 
+/// Longest name shown with a comment, in characters.
+const MAX_NAME_LENGTH: usize = 32;
+/// Starts the names of senders without one. Chosen names can't start with it.
+const ANONYMOUS_PREFIX: &str = "ID:";
+
 #[derive(Item)]
 struct WSConnection {
     #[dynomite(partition_key)]
@@ -44,6 +49,9 @@ struct CustomBody {
     action: String,
     message: String,
     channel: String,
+    /// Shown with the comment if given, the sender is anonymous otherwise.
+    #[serde(default)]
+    name: Option<String>,
 }
 
 #[derive(Serialize, Clone)]
@@ -66,7 +74,12 @@ fn my_handler(e: CustomEvent, c: lambda::Context) -> Result<CustomOutput, Handle
     let endpoint_url = format!("https://{}/{}", domain_name, stage);
 
     let body = serde_json::from_str::<CustomBody>(&e.body).expect("malformed data");
-    let message = body.message;
+    let author = body
+        .name
+        .as_deref()
+        .and_then(display_name)
+        .unwrap_or_else(|| anonymous_id(&connection_id));
+    let message = with_author(&body.message, author);
     let channel = body.channel;
 
     let client = DynamoDbClient::new(Region::ApNortheast1);
@@ -119,3 +132,95 @@ fn my_handler(e: CustomEvent, c: lambda::Context) -> Result<CustomOutput, Handle
         }
     })
 }
+
+fn display_name(name: &str) -> Option<String> {
+    let name: String = name
+        .trim()
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_NAME_LENGTH)
+        .collect();
+    let impersonating = name
+        .get(..ANONYMOUS_PREFIX.len())
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(ANONYMOUS_PREFIX));
+    if name.is_empty() || impersonating {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+/// The same for every comment sent over one connection, without telling who it is.
+fn anonymous_id(connection_id: &str) -> String {
+    // FNV-1a, which unlike `DefaultHasher` is the same on every build
+    let hash = connection_id.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{}{:08x}", ANONYMOUS_PREFIX, hash as u32)
+}
+
+/// Puts `author` in the message, which is either a JSON object with a `body` or plain text.
+fn with_author(message: &str, author: String) -> String {
+    let mut payload = match serde_json::from_str::<Value>(message) {
+        Ok(Value::Object(object)) if object.contains_key("body") => Value::Object(object),
+        _ => json!({ "body": message }),
+    };
+    payload["author"] = Value::String(author);
+    payload.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_trimmed_and_capped() {
+        assert_eq!(display_name("  kazz  "), Some("kazz".to_string()));
+        assert_eq!(display_name("a\u{7}b\nc"), Some("abc".to_string()));
+        assert_eq!(
+            display_name(&"あ".repeat(40)),
+            Some("あ".repeat(MAX_NAME_LENGTH))
+        );
+        assert_eq!(display_name(""), None);
+        assert_eq!(display_name(" \u{1b}\r\n "), None);
+    }
+
+    #[test]
+    fn names_cannot_pass_for_anonymous_ids() {
+        assert_eq!(display_name("ID:1a2b3c4d"), None);
+        assert_eq!(display_name("id:x"), None);
+        assert_eq!(display_name(" Id:x"), None);
+        assert_eq!(display_name("IDx"), Some("IDx".to_string()));
+    }
+
+    #[test]
+    fn anonymous_ids_are_stable_per_connection() {
+        let id = anonymous_id("abc=");
+        assert_eq!(id, anonymous_id("abc="));
+        assert_ne!(id, anonymous_id("abd="));
+        assert!(id.starts_with(ANONYMOUS_PREFIX));
+        assert_eq!(id.len(), ANONYMOUS_PREFIX.len() + 8);
+    }
+
+    #[test]
+    fn plain_text_is_wrapped() {
+        let message: Value = serde_json::from_str(&with_author("hi", "kazz".into())).unwrap();
+        assert_eq!(message, json!({ "body": "hi", "author": "kazz" }));
+
+        // JSON without a body is text too
+        let message: Value =
+            serde_json::from_str(&with_author("{\"a\":1}", "kazz".into())).unwrap();
+        assert_eq!(message, json!({ "body": "{\"a\":1}", "author": "kazz" }));
+    }
+
+    #[test]
+    fn the_author_is_overwritten() {
+        let sent = r#"{"body":"hi","command":"big","author":"admin"}"#;
+        let message: Value =
+            serde_json::from_str(&with_author(sent, "ID:1234abcd".into())).unwrap();
+        assert_eq!(
+            message,
+            json!({ "body": "hi", "command": "big", "author": "ID:1234abcd" })
+        );
+    }
+}
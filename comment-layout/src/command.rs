@@ -0,0 +1,227 @@
+//! Niconico style comment commands, e.g. `big red`.
+
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Command {
+    pub size: Size,
+    pub color: Color,
+    pub position: Position,
+}
+
+/// Where a comment is shown. The names are niconico's, `scroll`, `top` and `bottom` work too.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Position {
+    /// `naka`, scrolls from right to left.
+    #[default]
+    Scroll,
+    /// `ue`, centered and stacked from the top for a while.
+    Top,
+    /// `shita`, centered and stacked from the bottom for a while.
+    Bottom,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Size {
+    Small,
+    #[default]
+    Medium,
+    Big,
+}
+
+/// RGB.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Color(pub u8, pub u8, pub u8);
+
+const COLORS: &[(&str, Color)] = &[
+    ("white", Color(0xff, 0xff, 0xff)),
+    ("red", Color(0xff, 0x00, 0x00)),
+    ("pink", Color(0xff, 0x80, 0x80)),
+    ("orange", Color(0xff, 0xc0, 0x00)),
+    ("yellow", Color(0xff, 0xff, 0x00)),
+    ("green", Color(0x00, 0xff, 0x00)),
+    ("cyan", Color(0x00, 0xff, 0xff)),
+    ("blue", Color(0x00, 0x00, 0xff)),
+    ("purple", Color(0xc0, 0x00, 0xff)),
+    ("black", Color(0x00, 0x00, 0x00)),
+];
+
+impl Command {
+    /// Parses whitespace separated words. Unknown words are ignored and later words win.
+    pub fn parse(command: &str) -> Self {
+        let mut parsed = Command::default();
+        for word in command.split_whitespace() {
+            if let Some(size) = Size::from_name(word) {
+                parsed.size = size;
+            } else if let Some(position) = Position::from_name(word) {
+                parsed.position = position;
+            } else if let Some(color) = Color::from_name(word) {
+                parsed.color = color;
+            }
+        }
+        parsed
+    }
+}
+
+/// Formats as words [`Command::parse`] understands, leaving out defaults.
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut words = Vec::new();
+        if self.size != Size::default() {
+            words.push(self.size.name().to_string());
+        }
+        if self.color != Color::default() {
+            words.push(self.color.to_string());
+        }
+        if self.position != Position::default() {
+            words.push(self.position.name().to_string());
+        }
+        write!(f, "{}", words.join(" "))
+    }
+}
+
+impl Size {
+    pub const ALL: [Size; 3] = [Size::Small, Size::Medium, Size::Big];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Size::ALL.iter().cloned().find(|size| size.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Size::Small => "small",
+            Size::Medium => "medium",
+            Size::Big => "big",
+        }
+    }
+
+    /// Font size relative to `Medium`.
+    pub fn scale(self) -> f32 {
+        match self {
+            Size::Small => 0.625,
+            Size::Medium => 1.0,
+            Size::Big => 1.625,
+        }
+    }
+}
+
+impl Position {
+    pub const ALL: [Position; 3] = [Position::Scroll, Position::Top, Position::Bottom];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "naka" | "scroll" => Some(Position::Scroll),
+            "ue" | "top" => Some(Position::Top),
+            "shita" | "bottom" => Some(Position::Bottom),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Position::Scroll => "naka",
+            Position::Top => "ue",
+            Position::Bottom => "shita",
+        }
+    }
+}
+
+impl Color {
+    /// Every color that has a name, in palette order.
+    pub fn named() -> impl Iterator<Item = (&'static str, Color)> {
+        COLORS.iter().cloned()
+    }
+
+    /// A color name like `red`, or `#rrggbb`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        if let Some(hex) = name.strip_prefix('#') {
+            if hex.len() != 6 || !hex.is_ascii() {
+                return None;
+            }
+            let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+            return Some(Color(channel(0)?, channel(2)?, channel(4)?));
+        }
+        COLORS
+            .iter()
+            .find(|&&(color_name, _)| color_name == name)
+            .map(|&(_, color)| color)
+    }
+
+    pub fn name(self) -> Option<&'static str> {
+        COLORS
+            .iter()
+            .find(|&&(_, color)| color == self)
+            .map(|&(name, _)| name)
+    }
+
+    /// Normalized RGBA, opaque.
+    pub fn to_rgba(self) -> [f32; 4] {
+        let Color(r, g, b) = self;
+        [
+            f32::from(r) / 255.0,
+            f32::from(g) / 255.0,
+            f32::from(b) / 255.0,
+            1.0,
+        ]
+    }
+}
+
+impl Default for Color {
+    fn default() -> Self {
+        Color(0xff, 0xff, 0xff)
+    }
+}
+
+/// The color name if it has one, `#rrggbb` otherwise.
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "#{:02x}{:02x}{:02x}", self.0, self.1, self.2),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_known_words_and_ignores_the_rest() {
+        assert_eq!(
+            Command::parse("big  what red"),
+            Command {
+                size: Size::Big,
+                color: Color(0xff, 0x00, 0x00),
+                position: Position::Scroll,
+            }
+        );
+        assert_eq!(Command::parse(""), Command::default());
+    }
+
+    #[test]
+    fn parses_positions_and_their_english_names() {
+        assert_eq!(Command::parse("ue").position, Position::Top);
+        assert_eq!(Command::parse("bottom red").position, Position::Bottom);
+        assert_eq!(Command::parse("shita naka").position, Position::Scroll);
+    }
+
+    #[test]
+    fn parses_hex_colors() {
+        assert_eq!(Color::from_name("#12abEF"), Some(Color(0x12, 0xab, 0xef)));
+        assert_eq!(Color::from_name("#12ab"), None);
+        assert_eq!(Color::from_name("#12abzz"), None);
+    }
+
+    #[test]
+    fn formats_back_to_a_parsable_command() {
+        let command = Command {
+            size: Size::Small,
+            color: Color(0x12, 0x34, 0x56),
+            position: Position::Bottom,
+        };
+        assert_eq!(command.to_string(), "small #123456 shita");
+        assert_eq!(Command::parse(&command.to_string()), command);
+        assert_eq!(Command::default().to_string(), "");
+    }
+}
@@ -0,0 +1,429 @@
+//! Where comments are on screen at a given time, without drawing anything.
+//!
+//! Comments enter at the right edge and scroll left at a constant speed. The screen is split
+//! into horizontal lanes; a new comment takes the topmost lane whose previous comment has fully
+//! entered the screen, so comments in a lane never overlap. When every lane is busy, the lane
+//! that frees up first is reused.
+//!
+//! Top and bottom comments stay centered for [`Config::fixed_duration`] instead. They stack
+//! downwards from the top or upwards from the bottom with lanes of their own, picked the same
+//! way, so they may cover scrolling comments but not each other.
+//!
+//! Time is measured in seconds from any origin the caller likes, as long as it is used
+//! consistently.
+
+pub mod command;
+
+pub use command::{Command, Position};
+
+/// Logical pixels per second comments scroll at on the desktop overlay.
+pub const SCROLL_SPEED: f32 = 100.0;
+/// Seconds top and bottom comments stay on screen on the desktop overlay.
+pub const FIXED_DURATION: f64 = 3.0;
+/// The desktop overlay's default font size of `medium` comments, in logical pixels.
+pub const FONT_SIZE: f32 = 50.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Config {
+    /// Size of the area comments scroll over, in pixels.
+    pub width: f32,
+    pub height: f32,
+    /// Anything but a positive size leaves a single lane.
+    pub lane_height: f32,
+    /// Pixels per second.
+    pub speed: f32,
+    /// Seconds top and bottom comments stay on screen.
+    pub fixed_duration: f64,
+}
+
+pub struct Layout<T> {
+    config: Config,
+    /// Time at which each lane can take a new comment, for every position. Top and bottom lanes
+    /// are counted from their own edge.
+    scroll_lanes: Vec<f64>,
+    top_lanes: Vec<f64>,
+    bottom_lanes: Vec<f64>,
+    comments: Vec<Entry<T>>,
+}
+
+struct Entry<T> {
+    item: T,
+    position: Position,
+    /// Where the comment starts; fixed comments stay there.
+    x: f32,
+    y: f32,
+    /// Pixels per second, as configured when the comment was added.
+    speed: f32,
+    spawned: f64,
+    lifetime: f64,
+}
+
+/// A comment's position at some point in time.
+#[derive(Debug, PartialEq)]
+pub struct Placement<'a, T> {
+    pub item: &'a T,
+    /// Top left corner.
+    pub x: f32,
+    pub y: f32,
+    pub position: Position,
+    /// Seconds since the comment entered the screen.
+    pub elapsed: f64,
+    /// Seconds from entering to leaving the screen.
+    pub lifetime: f64,
+}
+
+impl<T> Layout<T> {
+    pub fn new(config: Config) -> Self {
+        let lanes = vec![f64::NEG_INFINITY; lane_count(&config)];
+        Layout {
+            scroll_lanes: lanes.clone(),
+            top_lanes: lanes.clone(),
+            bottom_lanes: lanes,
+            config,
+            comments: Vec::new(),
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Changes the area, the lanes or the speed. Comments already on screen keep their lanes
+    /// and speed.
+    pub fn set_config(&mut self, config: Config) {
+        let count = lane_count(&config);
+        for lanes in self.all_lanes() {
+            lanes.resize(count, f64::NEG_INFINITY);
+        }
+        self.config = config;
+    }
+
+    /// Adds a scrolling comment of the given size entering the screen at `time`, returns its lane.
+    pub fn push(&mut self, item: T, width: f32, height: f32, time: f64) -> usize {
+        self.place(item, Position::Scroll, width, height, time)
+    }
+
+    /// Adds a comment of the given size at `position`, returns its lane counted from the edge
+    /// its stack starts at.
+    pub fn place(
+        &mut self,
+        item: T,
+        position: Position,
+        width: f32,
+        height: f32,
+        time: f64,
+    ) -> usize {
+        let Config {
+            width: screen_width,
+            height: screen_height,
+            lane_height,
+            speed: config_speed,
+            fixed_duration,
+        } = self.config;
+        let speed = f64::from(config_speed);
+
+        let lanes = match position {
+            Position::Scroll => &mut self.scroll_lanes,
+            Position::Top => &mut self.top_lanes,
+            Position::Bottom => &mut self.bottom_lanes,
+        };
+        let span = ((height / lane_height).ceil() as usize).clamp(1, lanes.len());
+        let lane = find_lanes(lanes, span, time);
+
+        let (x, y, lifetime, free_at) = match position {
+            Position::Scroll => (
+                screen_width,
+                lane as f32 * lane_height,
+                f64::from(screen_width + width) / speed,
+                time + f64::from(width) / speed,
+            ),
+            Position::Top => (
+                (screen_width - width) / 2.0,
+                lane as f32 * lane_height,
+                fixed_duration,
+                time + fixed_duration,
+            ),
+            Position::Bottom => (
+                (screen_width - width) / 2.0,
+                screen_height - (lane + span) as f32 * lane_height,
+                fixed_duration,
+                time + fixed_duration,
+            ),
+        };
+        for lane_free_at in &mut lanes[lane..lane + span] {
+            *lane_free_at = free_at;
+        }
+
+        self.comments.push(Entry {
+            item,
+            position,
+            x,
+            y,
+            speed: config_speed,
+            spawned: time,
+            lifetime,
+        });
+
+        lane
+    }
+
+    /// Drops comments that have left the screen by `time`.
+    pub fn expire(&mut self, time: f64) {
+        self.comments
+            .retain(|comment| time - comment.spawned < comment.lifetime);
+    }
+
+    pub fn clear(&mut self) {
+        self.comments.clear();
+        for lanes in self.all_lanes() {
+            for lane in lanes.iter_mut() {
+                *lane = f64::NEG_INFINITY;
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.comments.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.comments.len()
+    }
+
+    /// Positions of every comment at `time`, in the order they were pushed.
+    pub fn positions(&self, time: f64) -> impl Iterator<Item = Placement<'_, T>> {
+        self.comments.iter().map(move |comment| {
+            let elapsed = time - comment.spawned;
+            let x = match comment.position {
+                Position::Scroll => comment.x - (elapsed * f64::from(comment.speed)) as f32,
+                Position::Top | Position::Bottom => comment.x,
+            };
+            Placement {
+                item: &comment.item,
+                x,
+                y: comment.y,
+                position: comment.position,
+                elapsed,
+                lifetime: comment.lifetime,
+            }
+        })
+    }
+
+    fn all_lanes(&mut self) -> [&mut Vec<f64>; 3] {
+        [
+            &mut self.scroll_lanes,
+            &mut self.top_lanes,
+            &mut self.bottom_lanes,
+        ]
+    }
+}
+
+/// First run of `span` lanes that are all free at `time`, or the one that frees up first.
+fn find_lanes(lanes: &[f64], span: usize, time: f64) -> usize {
+    let busy_until = |lane: usize| {
+        lanes[lane..lane + span]
+            .iter()
+            .cloned()
+            .fold(f64::NEG_INFINITY, f64::max)
+    };
+
+    let candidates = 0..=lanes.len() - span;
+    candidates
+        .clone()
+        .find(|&lane| busy_until(lane) <= time)
+        .or_else(|| candidates.min_by(|&a, &b| busy_until(a).partial_cmp(&busy_until(b)).unwrap()))
+        .unwrap_or(0)
+}
+
+/// More than any screen fits, to keep absurd configurations from allocating without bound.
+const MAX_LANES: usize = 1024;
+
+fn lane_count(config: &Config) -> usize {
+    let lanes = (config.height / config.lane_height).floor();
+    if config.lane_height > 0.0 && lanes.is_finite() && lanes >= 1.0 {
+        (lanes as usize).min(MAX_LANES)
+    } else {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout() -> Layout<&'static str> {
+        Layout::new(Config {
+            width: 1000.0,
+            height: 300.0,
+            lane_height: 100.0,
+            speed: 100.0,
+            fixed_duration: 3.0,
+        })
+    }
+
+    #[test]
+    fn comments_scroll_left_at_speed() {
+        let mut layout = layout();
+        layout.push("a", 200.0, 50.0, 10.0);
+
+        let placement = layout.positions(12.5).next().unwrap();
+        assert_eq!(placement.x, 750.0);
+        assert_eq!(placement.y, 0.0);
+        assert_eq!(placement.elapsed, 2.5);
+        assert_eq!(placement.lifetime, 12.0);
+    }
+
+    #[test]
+    fn busy_lanes_are_skipped() {
+        let mut layout = layout();
+        assert_eq!(layout.push("a", 200.0, 50.0, 0.0), 0);
+        assert_eq!(layout.push("b", 200.0, 50.0, 1.0), 1);
+        assert_eq!(layout.push("c", 200.0, 50.0, 1.5), 2);
+    }
+
+    #[test]
+    fn lane_is_reused_once_the_tail_entered_the_screen() {
+        let mut layout = layout();
+        layout.push("a", 200.0, 50.0, 0.0);
+        assert_eq!(layout.push("b", 200.0, 50.0, 1.9), 1);
+        assert_eq!(layout.push("c", 200.0, 50.0, 2.0), 0);
+    }
+
+    #[test]
+    fn full_screen_reuses_the_lane_that_frees_up_first() {
+        let mut layout = layout();
+        layout.push("a", 500.0, 50.0, 0.0);
+        layout.push("b", 100.0, 50.0, 0.0);
+        layout.push("c", 300.0, 50.0, 0.0);
+        assert_eq!(layout.push("d", 100.0, 50.0, 0.5), 1);
+    }
+
+    #[test]
+    fn tall_comments_take_several_lanes() {
+        let mut layout = layout();
+        layout.push("a", 200.0, 50.0, 0.0);
+        assert_eq!(layout.push("b", 400.0, 150.0, 0.0), 1);
+        assert_eq!(layout.push("c", 200.0, 50.0, 2.5), 0);
+        assert_eq!(layout.push("d", 200.0, 50.0, 3.0), 1);
+        assert_eq!(layout.push("e", 200.0, 50.0, 4.0), 2);
+    }
+
+    #[test]
+    fn comments_expire_after_leaving_the_screen() {
+        let mut layout = layout();
+        layout.push("a", 200.0, 50.0, 0.0);
+        layout.push("b", 200.0, 50.0, 5.0);
+
+        layout.expire(11.9);
+        assert_eq!(layout.len(), 2);
+
+        layout.expire(12.0);
+        let items: Vec<_> = layout.positions(12.0).map(|p| *p.item).collect();
+        assert_eq!(items, vec!["b"]);
+
+        layout.expire(17.0);
+        assert!(layout.is_empty());
+    }
+
+    #[test]
+    fn resizing_keeps_comments_in_place() {
+        let mut layout = layout();
+        layout.push("a", 200.0, 50.0, 0.0);
+        layout.set_config(Config {
+            width: 2000.0,
+            height: 600.0,
+            ..*layout.config()
+        });
+
+        let placement = layout.positions(1.0).next().unwrap();
+        assert_eq!(placement.x, 900.0);
+        assert_eq!(layout.push("b", 200.0, 50.0, 1.0), 1);
+        assert_eq!(layout.positions(1.0).nth(1).unwrap().x, 2000.0);
+    }
+
+    #[test]
+    fn changing_the_speed_keeps_comments_moving_as_they_were() {
+        let mut layout = layout();
+        layout.push("a", 200.0, 50.0, 0.0);
+        layout.set_config(Config {
+            speed: 50.0,
+            ..*layout.config()
+        });
+        layout.push("b", 200.0, 50.0, 1.0);
+
+        let xs: Vec<_> = layout.positions(3.0).map(|p| p.x).collect();
+        assert_eq!(xs, vec![700.0, 900.0]);
+        layout.expire(11.9);
+        assert_eq!(layout.len(), 2);
+        layout.expire(12.0);
+        assert_eq!(layout.len(), 1);
+    }
+
+    #[test]
+    fn fixed_comments_stay_centered_until_their_duration_ends() {
+        let mut layout = layout();
+        layout.place("a", Position::Top, 200.0, 50.0, 10.0);
+
+        let placement = layout.positions(12.0).next().unwrap();
+        assert_eq!((placement.x, placement.y), (400.0, 0.0));
+        assert_eq!(placement.position, Position::Top);
+
+        layout.expire(12.9);
+        assert_eq!(layout.len(), 1);
+        layout.expire(13.0);
+        assert!(layout.is_empty());
+    }
+
+    #[test]
+    fn top_and_bottom_comments_stack_from_their_edges() {
+        let mut layout = layout();
+        layout.place("a", Position::Top, 200.0, 50.0, 0.0);
+        layout.place("b", Position::Top, 200.0, 50.0, 1.0);
+        layout.place("c", Position::Bottom, 200.0, 50.0, 1.0);
+        layout.place("d", Position::Bottom, 200.0, 150.0, 1.0);
+
+        let ys: Vec<_> = layout.positions(1.0).map(|p| p.y).collect();
+        assert_eq!(ys, vec![0.0, 100.0, 200.0, 0.0]);
+    }
+
+    #[test]
+    fn fixed_lanes_are_free_again_after_the_duration() {
+        let mut layout = layout();
+        assert_eq!(layout.place("a", Position::Bottom, 200.0, 50.0, 0.0), 0);
+        assert_eq!(layout.place("b", Position::Bottom, 200.0, 50.0, 2.9), 1);
+        assert_eq!(layout.place("c", Position::Bottom, 200.0, 50.0, 3.0), 0);
+        // scrolling comments don't care about fixed ones
+        assert_eq!(layout.push("d", 200.0, 50.0, 3.0), 0);
+    }
+
+    #[test]
+    fn degenerate_lane_heights_leave_one_lane() {
+        for &(height, lane_height) in &[
+            (300.0, 0.0),
+            (300.0, -10.0),
+            (300.0, f32::NAN),
+            (f32::INFINITY, 100.0),
+            (0.0, 100.0),
+        ] {
+            let mut layout = Layout::new(Config {
+                width: 1000.0,
+                height,
+                lane_height,
+                speed: 100.0,
+                fixed_duration: 3.0,
+            });
+            assert_eq!(layout.push("a", 200.0, 50.0, 0.0), 0);
+            assert_eq!(layout.place("b", Position::Bottom, 200.0, 50.0, 0.0), 0);
+        }
+    }
+
+    #[test]
+    fn lanes_are_capped() {
+        let mut layout = layout();
+        layout.set_config(Config {
+            lane_height: 1e-6,
+            ..*layout.config()
+        });
+        assert_eq!(lane_count(layout.config()), MAX_LANES);
+    }
+}